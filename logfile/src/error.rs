@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// Why a log file couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The log file couldn't be opened.
+    Open {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Reading the log file failed part way through.
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+impl LoadError {
+    /// Wraps an error opening the log file at `path`.
+    pub fn open(path: &str, source: std::io::Error) -> Self {
+        LoadError::Open {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    /// Wraps an error reading the log file at `path`.
+    pub fn read(path: &str, source: std::io::Error) -> Self {
+        LoadError::Read {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Open { path, source } => write!(f, "can't open {path}: {source}"),
+            LoadError::Read { path, source } => write!(f, "error reading {path}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Open { source, .. } | LoadError::Read { source, .. } => Some(source),
+        }
+    }
+}
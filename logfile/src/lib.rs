@@ -0,0 +1,140 @@
+//! Opens log files for the viewers, decompressing them on the fly.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+mod error;
+mod lines;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub use error::LoadError;
+pub use lines::{lines, read_lines, Lines, Loaded};
+
+/// How a log file is compressed, as told by its first bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Recognizes the magic bytes at the start of a file.
+    pub fn detect(start: &[u8]) -> Self {
+        if start.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if start.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wraps `reader` so it yields the decompressed bytes.
+    pub fn decoder<'a>(
+        self,
+        reader: impl BufRead + Send + 'a,
+    ) -> std::io::Result<Box<dyn BufRead + Send + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => {
+                Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+            }
+            Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+            Compression::Xz => Box::new(BufReader::new(
+                xz2::bufread::XzDecoder::new_multi_decoder(reader),
+            )),
+        })
+    }
+}
+
+/// Opens the file at `path` for reading, decompressing it if it starts with
+/// gzip, zstd or xz magic bytes, whatever its extension. `-` is stdin.
+///
+/// Detecting the compression waits for the first bytes, so with stdin this
+/// blocks until there is some input.
+pub fn open(path: impl AsRef<Path>) -> std::io::Result<Box<dyn BufRead + Send>> {
+    if path.as_ref() == Path::new(STDIN) {
+        return decompress(BufReader::new(std::io::stdin()));
+    }
+    decompress(BufReader::new(File::open(path)?))
+}
+
+/// Opens and reads the whole log file at `path`.
+pub fn load(path: &str) -> Result<Loaded, LoadError> {
+    let reader = open(path).map_err(|source| LoadError::open(path, source))?;
+    read_lines(reader).map_err(|source| LoadError::read(path, source))
+}
+
+/// The path that stands for stdin.
+pub const STDIN: &str = "-";
+
+fn decompress(
+    mut reader: impl BufRead + Send + 'static,
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    let compression = Compression::detect(reader.fill_buf()?);
+    compression.decoder(reader)
+}
+
+/// Tells how the file at `path` is compressed.
+pub fn compression(path: impl AsRef<Path>) -> std::io::Result<Compression> {
+    let mut start = Vec::with_capacity(6);
+    File::open(path)?.take(6).read_to_end(&mut start)?;
+    Ok(Compression::detect(&start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TEXT: &str = "+ Section 1\nERROR one\n++ Section 1.1\nok\n";
+
+    fn decompress(bytes: &[u8]) -> Vec<String> {
+        let decoder = Compression::detect(bytes).decoder(bytes).unwrap();
+        read_lines(decoder).unwrap().lines
+    }
+
+    #[test]
+    fn plain_text_passes_through() {
+        assert_eq!(Compression::detect(TEXT.as_bytes()), Compression::None);
+        assert_eq!(decompress(TEXT.as_bytes()).len(), 4);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn load_names_the_file_it_failed_on() {
+        let err = load("no/such.log").unwrap_err();
+        assert!(matches!(err, LoadError::Open { .. }));
+        assert!(err.to_string().starts_with("can't open no/such.log: "));
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(Compression::detect(&bytes), Compression::Gzip);
+        assert_eq!(decompress(&bytes), TEXT.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn zstd() {
+        let bytes = zstd::encode_all(TEXT.as_bytes(), 0).unwrap();
+        assert_eq!(Compression::detect(&bytes), Compression::Zstd);
+        assert_eq!(decompress(&bytes), TEXT.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn xz() {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(Compression::detect(&bytes), Compression::Xz);
+        assert_eq!(decompress(&bytes), TEXT.lines().collect::<Vec<_>>());
+    }
+}
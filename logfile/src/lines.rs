@@ -0,0 +1,81 @@
+use std::io::BufRead;
+
+/// Lines read from a log, decoded as UTF-8.
+#[derive(Debug, Default)]
+pub struct Loaded {
+    pub lines: Vec<String>,
+    pub invalid: usize, // lines that weren't valid UTF-8 and were decoded lossily
+}
+
+/// Reads all lines like `BufRead::lines`, but replaces invalid UTF-8 instead
+/// of failing on it.
+pub fn read_lines(reader: impl BufRead) -> std::io::Result<Loaded> {
+    let mut loaded = Loaded::default();
+    for line in lines(reader) {
+        let (line, valid) = line?;
+        if !valid {
+            loaded.invalid += 1;
+        }
+        loaded.lines.push(line);
+    }
+    Ok(loaded)
+}
+
+/// Iterates over lines like `BufRead::lines`, but replaces invalid UTF-8
+/// instead of failing on it. Each line comes with whether it was valid.
+pub fn lines<R: BufRead>(reader: R) -> Lines<R> {
+    Lines {
+        reader,
+        bytes: Vec::new(),
+    }
+}
+
+/// The iterator returned by [`lines`].
+#[derive(Debug)]
+pub struct Lines<R> {
+    reader: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = std::io::Result<(String, bool)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_until(b'\n', &mut self.bytes) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(err) => return Some(Err(err)),
+        }
+        if self.bytes.last() == Some(&b'\n') {
+            self.bytes.pop();
+            if self.bytes.last() == Some(&b'\r') {
+                self.bytes.pop();
+            }
+        }
+        Some(Ok(
+            match String::from_utf8(std::mem::take(&mut self.bytes)) {
+                Ok(line) => (line, true),
+                Err(err) => (String::from_utf8_lossy(err.as_bytes()).into_owned(), false),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_like_buf_read_lines() {
+        let loaded = read_lines(&b"a\r\nb\n\nc"[..]).unwrap();
+        assert_eq!(loaded.lines, ["a", "b", "", "c"]);
+        assert_eq!(loaded.invalid, 0);
+    }
+
+    #[test]
+    fn decodes_invalid_utf8_lossily() {
+        let loaded = read_lines(&b"ok\nbad \xff byte\n"[..]).unwrap();
+        assert_eq!(loaded.lines, ["ok", "bad \u{fffd} byte"]);
+        assert_eq!(loaded.invalid, 1);
+    }
+}
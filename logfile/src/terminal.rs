@@ -0,0 +1,32 @@
+//! Sets up and restores the terminal for the viewers.
+
+/// Puts the terminal into raw mode on the alternate screen, with mouse capture.
+pub fn init_terminal() -> std::io::Result<()> {
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(
+        std::io::stdout(),
+        crossterm::terminal::EnterAlternateScreen,
+        crossterm::event::EnableMouseCapture
+    )
+}
+
+/// Undoes `init_terminal`, leaving the terminal as the shell expects it.
+pub fn restore_terminal() -> std::io::Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(
+        std::io::stdout(),
+        crossterm::terminal::LeaveAlternateScreen,
+        crossterm::event::DisableMouseCapture,
+        crossterm::cursor::Show
+    )
+}
+
+/// Restores the terminal before a panic message is printed, so it's
+/// readable and the shell isn't left in raw mode.
+pub fn restore_on_panic() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+}
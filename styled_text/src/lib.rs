@@ -1,15 +1,13 @@
 use clap::Parser;
 use crossterm::event::{Event, KeyCode};
+use logfile::LoadError;
 use ratatui::backend::Backend;
 use ratatui::prelude::{Line, Stylize, Terminal, Text};
 
 use ratatui::widgets::{Block, Paragraph};
 use ratatui::Frame;
+use std::fmt;
 use std::time::{Duration, Instant};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about)]
@@ -19,25 +17,64 @@ pub struct Config {
     pub filename: String,
 }
 
+/// Why the viewer couldn't start or had to stop.
+#[derive(Debug)]
+pub enum AppError {
+    /// The log file couldn't be opened or read.
+    Load(LoadError),
+    /// Terminal input or output failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Load(err) => write!(f, "{err}"),
+            AppError::Io(source) => write!(f, "{source}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Load(err) => err.source(),
+            AppError::Io(source) => Some(source),
+        }
+    }
+}
+
+impl From<LoadError> for AppError {
+    fn from(err: LoadError) -> Self {
+        AppError::Load(err)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Io(err)
+    }
+}
+
 #[must_use]
 pub struct App {
     pub filename: String, // name of the log file to view
     _lines: Vec<String>,
+    warning: Option<String>, // shown under the log if the file had invalid UTF-8
 }
 
 impl App {
-    pub fn new(config: &Config) -> Self {
-        let file = File::open(config.filename.clone()).expect("no such file");
-        let buf = BufReader::new(file);
-        let lines = buf
-            .lines()
-            .map(|l| l.expect("couldn't read the file lines"))
-            .collect();
+    pub fn new(config: &Config) -> Result<Self, AppError> {
+        let loaded = logfile::load(&config.filename)?;
+        let invalid = loaded.invalid;
 
-        Self {
+        Ok(Self {
             filename: config.filename.to_owned(),
-            _lines: lines,
-        }
+            _lines: loaded.lines,
+            warning: (invalid > 0).then(|| {
+                format!("{invalid} invalid UTF-8 line(s) shown with replacement characters")
+            }),
+        })
     }
 
     pub fn stylize<'a>(s: String) -> Line<'a> {
@@ -57,9 +94,12 @@ impl App {
             .clone()
             .into_iter()
             .map(App::stylize)
-            .map(Line::from)
             .collect();
-        let log = Paragraph::new(Text::from(lines)).block(Block::bordered().title("Log Lines"));
+        let mut block = Block::bordered().title("Log Lines");
+        if let Some(warning) = &self.warning {
+            block = block.title_bottom(Line::from(warning.as_str()).yellow());
+        }
+        let log = Paragraph::new(Text::from(lines)).block(block);
         frame.render_widget(log, frame.size());
     }
 }
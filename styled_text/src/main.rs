@@ -1,36 +1,27 @@
 use clap::Parser;
+use logfile::terminal::{init_terminal, restore_on_panic, restore_terminal};
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use styled_text::{run_app, App, Config};
+use styled_text::{run_app, App, AppError, Config};
 
 fn main() -> std::io::Result<()> {
     let config = Config::parse();
 
+    restore_on_panic();
+
     // Terminal initialization
-    crossterm::terminal::enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    crossterm::execute!(
-        stdout,
-        crossterm::terminal::EnterAlternateScreen,
-        crossterm::event::EnableMouseCapture
-    )?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    init_terminal()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
 
     // App
-    let app = App::new(&config);
-    let res = run_app(&mut terminal, app);
+    let res = App::new(&config).and_then(|app| run_app(&mut terminal, app).map_err(AppError::from));
 
-    // restore terminal
-    crossterm::terminal::disable_raw_mode()?;
-    crossterm::execute!(
-        terminal.backend_mut(),
-        crossterm::terminal::LeaveAlternateScreen,
-        crossterm::event::DisableMouseCapture
-    )?;
+    restore_terminal()?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
-        println!("{err:?}");
+        eprintln!("styled_text: {err}");
+        std::process::exit(1);
     }
 
     Ok(())
@@ -0,0 +1,109 @@
+//! Measures opening a large log: how long until the first frame can be drawn,
+//! how long the background index takes, and how much heap it needs, compared
+//! with reading every line into memory.
+//!
+//! `cargo bench --bench load`; set `TREETEST_BENCH_MB` to change the log size
+//! (default 1024). Fails if the first frame takes longer than `FIRST_FRAME`.
+
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+use treetest::{Rules, Tab};
+
+/// How long opening a log may take before the first frame can be drawn.
+const FIRST_FRAME: Duration = Duration::from_secs(1);
+
+fn main() -> std::io::Result<()> {
+    let megabytes: u64 = std::env::var("TREETEST_BENCH_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(1024);
+    let path = std::env::temp_dir().join(format!("treetest-bench-{}.log", std::process::id()));
+    let lines = write_log(&path, megabytes << 20)?;
+    let path_str = path.to_string_lossy().into_owned();
+    println!("{megabytes} MB log, {lines} lines");
+
+    let heap = rss_anon_kb();
+    let start = Instant::now();
+    let mut tab = Tab::new(
+        &path_str,
+        &Rules::default(),
+        &Regex::new("ERROR|FATAL|panicked").unwrap(),
+        None,
+    )
+    .expect("the log opens");
+    let first_frame = start.elapsed();
+    println!(
+        "open (first frame):     {:>8.3} s",
+        first_frame.as_secs_f64()
+    );
+    tab.finish_loading()?;
+    println!(
+        "index in background:    {:>8.3} s",
+        start.elapsed().as_secs_f64()
+    );
+    println!(
+        "  {} sections, {} error lines, heap +{} MB",
+        tab.outline.sections.len(),
+        tab.error_lines.lines.len(),
+        rss_anon_kb().saturating_sub(heap) >> 10
+    );
+    drop(tab);
+
+    let heap = rss_anon_kb();
+    let start = Instant::now();
+    let loaded = logfile::read_lines(logfile::open(&path)?)?;
+    println!(
+        "read into Vec<String>:  {:>8.3} s",
+        start.elapsed().as_secs_f64()
+    );
+    println!(
+        "  {} lines, heap +{} MB",
+        loaded.lines.len(),
+        rss_anon_kb().saturating_sub(heap) >> 10
+    );
+
+    std::fs::remove_file(&path)?;
+    if first_frame > FIRST_FRAME {
+        let error = format!("the first frame took over {} s", FIRST_FRAME.as_secs_f64());
+        return Err(std::io::Error::other(error));
+    }
+    Ok(())
+}
+
+/// Writes a log of about `size` bytes with nested sections and some errors.
+fn write_log(path: &std::path::Path, size: u64) -> std::io::Result<usize> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    let mut lines = 0;
+    while written < size {
+        let line = match lines % 10_000 {
+            0 => format!("+ Step {}\n", lines / 10_000),
+            2_500 | 7_500 => format!("++ Phase {}\n", lines / 2_500),
+            n if n % 997 == 0 => format!("12:00:{:02} ERROR something failed\n", n % 60),
+            n => format!(
+                "12:00:{:02} INFO line {lines} doing ordinary work\n",
+                n % 60
+            ),
+        };
+        out.write_all(line.as_bytes())?;
+        written += line.len() as u64;
+        lines += 1;
+    }
+    out.flush()?;
+    Ok(lines)
+}
+
+/// Resident anonymous memory (the heap, not the page cache) in KiB, on Linux.
+fn rss_anon_kb() -> u64 {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("RssAnon:"))
+                .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse().ok())
+        })
+        .unwrap_or(0)
+}
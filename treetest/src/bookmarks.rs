@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::LineStore;
+
+/// Tree identifier of the "Bookmarks" node. Its children are identified by
+/// their index in `Bookmarks::marks`.
+pub const BOOKMARKS: usize = usize::MAX;
+
+/// How far from its saved line a bookmark is looked for when the log changed.
+const RELOCATE_WINDOW: usize = 500;
+
+/// A marked log line, with an optional note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub line: usize, // index of the marked line
+    pub hash: u64,   // of the line's text, to find it again if lines move
+    pub note: String,
+}
+
+/// The bookmarks of a log, sorted by line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bookmarks {
+    pub marks: Vec<Bookmark>,
+}
+
+/// The sidecar file format; lines are numbered from 1 there, for people
+/// reading or writing it by hand.
+#[derive(Serialize, Deserialize, Default)]
+struct NotesFile {
+    #[serde(default, rename = "bookmark")]
+    bookmarks: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    line: usize,
+    hash: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    note: String,
+}
+
+impl Bookmarks {
+    /// Where the bookmarks of the log `filename` are kept, or `None` for stdin.
+    pub fn sidecar(filename: &str) -> Option<PathBuf> {
+        (filename != logfile::STDIN).then(|| PathBuf::from(format!("{filename}.notes.toml")))
+    }
+
+    /// Reads the bookmarks saved at `path`; there are none if it doesn't exist.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes the bookmarks to `path`, or removes it when there are none.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if self.marks.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+        std::fs::write(path, self.to_toml())
+    }
+
+    pub fn from_toml(text: &str) -> std::io::Result<Self> {
+        let file: NotesFile =
+            toml::from_str(text).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let mut marks = file
+            .bookmarks
+            .into_iter()
+            .map(|entry| {
+                let hash = u64::from_str_radix(&entry.hash, 16).map_err(|err| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("bad hash {:?} on line {}: {err}", entry.hash, entry.line),
+                    )
+                })?;
+                Ok(Bookmark {
+                    line: entry.line.saturating_sub(1),
+                    hash,
+                    note: entry.note,
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        marks.sort_by_key(|mark| mark.line);
+        marks.dedup_by_key(|mark| mark.line);
+        Ok(Self { marks })
+    }
+
+    pub fn to_toml(&self) -> String {
+        let file = NotesFile {
+            bookmarks: self
+                .marks
+                .iter()
+                .map(|mark| Entry {
+                    line: mark.line + 1,
+                    hash: format!("{:016x}", mark.hash),
+                    note: mark.note.clone(),
+                })
+                .collect(),
+        };
+        toml::to_string(&file).expect("bookmarks serialize")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// The index in `marks` of the bookmark on `line`.
+    pub fn find(&self, line: usize) -> Option<usize> {
+        self.marks
+            .binary_search_by_key(&line, |mark| mark.line)
+            .ok()
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.find(line).is_some()
+    }
+
+    /// Bookmarks `line`, whose text is `text`, or removes its bookmark.
+    /// Returns whether the line is bookmarked now.
+    pub fn toggle(&mut self, line: usize, text: &str) -> bool {
+        match self.marks.binary_search_by_key(&line, |mark| mark.line) {
+            Ok(index) => {
+                self.marks.remove(index);
+                false
+            }
+            Err(index) => {
+                let mark = Bookmark {
+                    line,
+                    hash: line_hash(text),
+                    note: String::new(),
+                };
+                self.marks.insert(index, mark);
+                true
+            }
+        }
+    }
+
+    /// Sets the note on `line`, bookmarking it first if it isn't yet.
+    pub fn set_note(&mut self, line: usize, text: &str, note: &str) {
+        if !self.contains(line) {
+            self.toggle(line, text);
+        }
+        if let Some(index) = self.find(line) {
+            self.marks[index].note = note.trim().to_owned();
+        }
+    }
+
+    /// The first bookmarked line after `line`, wrapping around.
+    pub fn next_after(&self, line: usize) -> Option<usize> {
+        let index = self.marks.partition_point(|mark| mark.line <= line);
+        self.marks
+            .get(index)
+            .or(self.marks.first())
+            .map(|mark| mark.line)
+    }
+
+    /// The last bookmarked line before `line`, wrapping around.
+    pub fn previous_before(&self, line: usize) -> Option<usize> {
+        let index = self.marks.partition_point(|mark| mark.line < line);
+        index
+            .checked_sub(1)
+            .and_then(|index| self.marks.get(index))
+            .or(self.marks.last())
+            .map(|mark| mark.line)
+    }
+
+    /// Moves each bookmark whose line no longer has the text it was made on
+    /// to the nearest line that does, so notes survive small edits to the log.
+    /// Bookmarks whose text can't be found nearby stay where they are.
+    pub fn relocate(&mut self, lines: &LineStore) {
+        for mark in &mut self.marks {
+            let matches =
+                |line: usize| line < lines.len() && line_hash(&lines.get(line)) == mark.hash;
+            if matches(mark.line) {
+                continue;
+            }
+            let found = (1..=RELOCATE_WINDOW).find_map(|distance| {
+                let before = mark
+                    .line
+                    .checked_sub(distance)
+                    .filter(|&line| matches(line));
+                before.or_else(|| Some(mark.line + distance).filter(|&line| matches(line)))
+            });
+            if let Some(line) = found {
+                mark.line = line;
+            }
+        }
+        self.marks.sort_by_key(|mark| mark.line);
+        self.marks.dedup_by_key(|mark| mark.line);
+    }
+}
+
+/// A hash of a line's text (64-bit FNV-1a), stable across platforms and
+/// versions so sidecar files can be shared.
+pub fn line_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(lines: &[&str]) -> LineStore {
+        LineStore::new(lines.iter().map(|line| line.to_string()).collect())
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.toggle(7, "ERROR disk full");
+        bookmarks.set_note(2, "+ Section 1", "start here");
+        let text = bookmarks.to_toml();
+        assert!(text.contains("line = 3"));
+        assert!(text.contains("note = \"start here\""));
+        assert_eq!(Bookmarks::from_toml(&text).unwrap(), bookmarks);
+        assert!(Bookmarks::from_toml("[[bookmark]]\nline = 1\nhash = \"xyz\"").is_err());
+    }
+
+    #[test]
+    fn toggles_and_wraps_around() {
+        let mut bookmarks = Bookmarks::default();
+        assert!(bookmarks.toggle(5, "a"));
+        assert!(bookmarks.toggle(1, "b"));
+        assert_eq!(bookmarks.next_after(1), Some(5));
+        assert_eq!(bookmarks.next_after(5), Some(1));
+        assert_eq!(bookmarks.previous_before(5), Some(1));
+        assert_eq!(bookmarks.previous_before(0), Some(5));
+        assert!(!bookmarks.toggle(5, "a"));
+        assert_eq!(bookmarks.marks.len(), 1);
+    }
+
+    #[test]
+    fn follows_moved_lines() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.toggle(1, "ERROR x");
+        bookmarks.toggle(2, "gone");
+        bookmarks.relocate(&store(&["new", "new", "a", "ERROR x", "b"]));
+        let lines: Vec<_> = bookmarks.marks.iter().map(|mark| mark.line).collect();
+        assert_eq!(lines, [2, 3]);
+    }
+}
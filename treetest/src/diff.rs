@@ -0,0 +1,203 @@
+/// Above this many cells in the alignment table, the differing middle of two
+/// sections is shown as changed as a whole rather than aligned line by line.
+const MAX_TABLE: usize = 4_000_000;
+
+/// How a row of a comparison differs between the two sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Same,
+    Changed, // on both sides, with different text
+    Removed, // only on the left
+    Added,   // only on the right
+}
+
+/// One row of a side-by-side comparison: the lines shown on each side, as
+/// indices into the slices that were compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffRow {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub edit: Edit,
+}
+
+/// Two sections shown side by side in the log pane, scrolled together.
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    pub left: usize,        // index of the marked section
+    pub right: usize,       // index of the section it was compared with
+    pub rows: Vec<DiffRow>, // line indices into the log
+    pub offset: usize,      // index of the first row shown
+}
+
+/// Aligns the lines of `left` and `right` for showing side by side. Lines
+/// are matched with their numbers masked, so timestamps and ids don't keep
+/// repeated runs from lining up; matched lines that still differ, and
+/// removed lines facing added ones, are shown as changed.
+pub fn diff<S: AsRef<str>>(left: &[S], right: &[S]) -> Vec<DiffRow> {
+    let left_keys: Vec<_> = left
+        .iter()
+        .map(|line| mask_numbers(line.as_ref()))
+        .collect();
+    let right_keys: Vec<_> = right
+        .iter()
+        .map(|line| mask_numbers(line.as_ref()))
+        .collect();
+    let pairs = align(&left_keys, &right_keys);
+
+    let mut rows = Vec::new();
+    let (mut l, mut r) = (0, 0);
+    for (next_l, next_r) in pairs.into_iter().chain([(left.len(), right.len())]) {
+        // Lines between matches face each other as long as both sides have some.
+        let (removed, added) = (next_l - l, next_r - r);
+        for offset in 0..removed.max(added) {
+            let row = match (offset < removed, offset < added) {
+                (true, true) => DiffRow {
+                    left: Some(l + offset),
+                    right: Some(r + offset),
+                    edit: Edit::Changed,
+                },
+                (true, false) => DiffRow {
+                    left: Some(l + offset),
+                    right: None,
+                    edit: Edit::Removed,
+                },
+                _ => DiffRow {
+                    left: None,
+                    right: Some(r + offset),
+                    edit: Edit::Added,
+                },
+            };
+            rows.push(row);
+        }
+        if next_l < left.len() {
+            let same = left[next_l].as_ref() == right[next_r].as_ref();
+            rows.push(DiffRow {
+                left: Some(next_l),
+                right: Some(next_r),
+                edit: if same { Edit::Same } else { Edit::Changed },
+            });
+        }
+        (l, r) = (next_l + 1, next_r + 1);
+    }
+    rows
+}
+
+/// The index pairs of a longest common subsequence of `left` and `right`.
+fn align(left: &[String], right: &[String]) -> Vec<(usize, usize)> {
+    let prefix = left.iter().zip(right).take_while(|(l, r)| l == r).count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let (left_middle, right_middle) = (
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+    );
+
+    let mut pairs: Vec<_> = (0..prefix).map(|index| (index, index)).collect();
+    let (n, m) = (left_middle.len(), right_middle.len());
+    if n * m <= MAX_TABLE {
+        // lengths[i][j]: LCS length of left_middle[i..] and right_middle[j..]
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        let at = |i: usize, j: usize| i * (m + 1) + j;
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[at(i, j)] = if left_middle[i] == right_middle[j] {
+                    lengths[at(i + 1, j + 1)] + 1
+                } else {
+                    lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if left_middle[i] == right_middle[j] {
+                pairs.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    pairs.extend(
+        (0..suffix).map(|index| (left.len() - suffix + index, right.len() - suffix + index)),
+    );
+    pairs
+}
+
+/// `line` with each run of digits replaced by a single `0`.
+fn mask_numbers(line: &str) -> String {
+    let mut masked = String::with_capacity(line.len());
+    let mut in_number = false;
+    for c in line.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                masked.push('0');
+            }
+            in_number = true;
+        } else {
+            masked.push(c);
+            in_number = false;
+        }
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edits(left: &[&str], right: &[&str]) -> Vec<(Option<usize>, Option<usize>, Edit)> {
+        diff(left, right)
+            .into_iter()
+            .map(|row| (row.left, row.right, row.edit))
+            .collect()
+    }
+
+    #[test]
+    fn aligns_added_removed_and_changed_lines() {
+        let left = [
+            "+ attempt 1",
+            "connect",
+            "10:00:01 send",
+            "timeout",
+            "close",
+        ];
+        let right = [
+            "+ attempt 2",
+            "connect",
+            "10:00:09 send",
+            "reply",
+            "ok",
+            "close",
+        ];
+        assert_eq!(
+            edits(&left, &right),
+            [
+                (Some(0), Some(0), Edit::Changed),
+                (Some(1), Some(1), Edit::Same),
+                (Some(2), Some(2), Edit::Changed),
+                (Some(3), Some(3), Edit::Changed),
+                (None, Some(4), Edit::Added),
+                (Some(4), Some(5), Edit::Same),
+            ]
+        );
+    }
+
+    #[test]
+    fn one_side_empty() {
+        assert_eq!(
+            edits(&["a", "b"], &[]),
+            [
+                (Some(0), None, Edit::Removed),
+                (Some(1), None, Edit::Removed)
+            ]
+        );
+        assert_eq!(edits(&[], &["a"]), [(None, Some(0), Edit::Added)]);
+    }
+}
@@ -0,0 +1,16 @@
+use std::process::Command;
+
+/// The command that opens `path` at `line` (counted from 1) in the user's
+/// editor: `$VISUAL`, `$EDITOR` or else `vi`, which may come with arguments.
+pub fn editor_command(path: &str, line: usize) -> Command {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(|editor| editor.to_string_lossy().into_owned())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_owned());
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().expect("editor is not blank"));
+    command.args(words).arg(format!("+{line}")).arg(path);
+    command
+}
@@ -0,0 +1,55 @@
+use logfile::LoadError;
+use std::fmt;
+
+/// Why the viewer couldn't start or had to stop.
+#[derive(Debug)]
+pub enum AppError {
+    /// A log file couldn't be opened or read.
+    Load(LoadError),
+    /// The section asked for with `--extract` isn't in the file.
+    NoSection { path: String, section: String },
+    /// The section rules given on the command line or in a rules file are invalid.
+    Rules(std::io::Error),
+    /// The `--error-pattern` regex doesn't compile.
+    ErrorPattern(regex::Error),
+    /// The `--group` regex doesn't compile or has no `key` capture.
+    GroupPattern(String),
+    /// Terminal input or output failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Load(err) => write!(f, "{err}"),
+            AppError::NoSection { path, section } => write!(f, "no section `{section}` in {path}"),
+            AppError::Rules(source) => write!(f, "invalid section rules: {source}"),
+            AppError::ErrorPattern(source) => write!(f, "invalid error pattern: {source}"),
+            AppError::GroupPattern(reason) => write!(f, "invalid group pattern: {reason}"),
+            AppError::Io(source) => write!(f, "{source}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Load(err) => err.source(),
+            AppError::Rules(source) | AppError::Io(source) => Some(source),
+            AppError::ErrorPattern(source) => Some(source),
+            AppError::NoSection { .. } | AppError::GroupPattern(_) => None,
+        }
+    }
+}
+
+impl From<LoadError> for AppError {
+    fn from(err: LoadError) -> Self {
+        AppError::Load(err)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Io(err)
+    }
+}
@@ -0,0 +1,99 @@
+use regex::Regex;
+
+/// The lines matching the error pattern, for jumping from one failure to the next.
+#[derive(Debug, Clone)]
+pub struct ErrorLines {
+    pub regex: Regex,
+    pub lines: Vec<usize>,      // sorted
+    pub current: Option<usize>, // index into `lines`
+}
+
+impl ErrorLines {
+    pub fn new<S: AsRef<str>>(regex: Regex, lines: impl IntoIterator<Item = S>) -> Self {
+        let mut errors = Self {
+            regex,
+            lines: Vec::new(),
+            current: None,
+        };
+        errors.extend(lines, 0);
+        errors
+    }
+
+    /// Adds the error lines among `lines`, whose first line has index `first`.
+    pub fn extend<S: AsRef<str>>(&mut self, lines: impl IntoIterator<Item = S>, first: usize) {
+        self.lines.extend(
+            lines
+                .into_iter()
+                .enumerate()
+                .filter(|(_, line)| self.regex.is_match(line.as_ref()))
+                .map(|(index, _)| first + index),
+        );
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.lines.binary_search(&line).is_ok()
+    }
+
+    pub fn current_line(&self) -> Option<usize> {
+        self.lines.get(self.current?).copied()
+    }
+
+    /// Moves to the error after the current one, or without one, the first
+    /// error at or after `line`. Wraps around.
+    pub fn next_from(&mut self, line: usize) -> Option<usize> {
+        let len = self.lines.len();
+        if len == 0 {
+            return None;
+        }
+        self.current = Some(match self.current {
+            Some(current) => (current + 1) % len,
+            None => self.lines.partition_point(|&error| error < line) % len,
+        });
+        self.current_line()
+    }
+
+    /// Moves to the error before the current one, or without one, the last
+    /// error before `line`. Wraps around.
+    pub fn previous_from(&mut self, line: usize) -> Option<usize> {
+        let len = self.lines.len();
+        if len == 0 {
+            return None;
+        }
+        self.current = Some(match self.current {
+            Some(current) => (current + len - 1) % len,
+            None => (self.lines.partition_point(|&error| error < line) + len - 1) % len,
+        });
+        self.current_line()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_lines() -> ErrorLines {
+        let lines = ["ok", "ERROR a", "ok", "FATAL b", "ok", "thread panicked"];
+        ErrorLines::new(Regex::new("ERROR|FATAL|panicked").unwrap(), lines)
+    }
+
+    #[test]
+    fn finds_error_lines() {
+        let errors = error_lines();
+        assert_eq!(errors.lines, [1, 3, 5]);
+        assert!(errors.contains(3));
+        assert!(!errors.contains(4));
+    }
+
+    #[test]
+    fn starts_from_the_given_line_and_wraps() {
+        let mut errors = error_lines();
+        assert_eq!(errors.next_from(2), Some(3));
+        assert_eq!(errors.next_from(0), Some(5));
+        assert_eq!(errors.next_from(0), Some(1));
+        assert_eq!(errors.previous_from(0), Some(5));
+
+        let mut errors = self::error_lines();
+        assert_eq!(errors.previous_from(3), Some(1));
+        assert_eq!(errors.previous_from(3), Some(5));
+    }
+}
@@ -0,0 +1,62 @@
+use crossterm::event::Event;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Where `run_app` takes its input from.
+pub trait EventSource {
+    /// The next event, or `None` if none arrived within `timeout`.
+    fn next(&mut self, timeout: Duration) -> std::io::Result<Option<Event>>;
+
+    /// Whether no more events will come, so the viewer should draw its last
+    /// frame and stop.
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Keys, mouse events and resizes from the terminal.
+#[derive(Debug, Default)]
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn next(&mut self, timeout: Duration) -> std::io::Result<Option<Event>> {
+        if crossterm::event::poll(timeout)? {
+            crossterm::event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// A fixed sequence of events, for driving the viewer from tests. Like a
+/// user, it pauses after each event until the viewer has drawn it, since tree
+/// navigation goes by what was last drawn.
+#[derive(Debug, Default)]
+pub struct ScriptedEvents {
+    events: VecDeque<Event>,
+    waiting: bool, // the next call waits for the last event to be drawn
+}
+
+impl ScriptedEvents {
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            waiting: false,
+        }
+    }
+}
+
+impl EventSource for ScriptedEvents {
+    fn next(&mut self, timeout: Duration) -> std::io::Result<Option<Event>> {
+        if std::mem::take(&mut self.waiting) {
+            std::thread::sleep(timeout);
+            return Ok(None);
+        }
+        self.waiting = true;
+        Ok(self.events.pop_front())
+    }
+
+    fn finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
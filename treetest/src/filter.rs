@@ -0,0 +1,34 @@
+/// Fuzzy-matches `query` against `text`: every query character has to appear
+/// in `text`, in order, ignoring case. Returns the char indices of the matched
+/// characters in `text`.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let mut positions = Vec::new();
+    let mut wanted = query.chars().flat_map(char::to_lowercase).peekable();
+    for (index, c) in text.chars().enumerate() {
+        let Some(&next) = wanted.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(next)) {
+            positions.push(index);
+            wanted.next();
+        }
+    }
+    wanted.peek().is_none().then_some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_in_order_ignoring_case() {
+        assert_eq!(fuzzy_match("sec2", "Section 2.1"), Some(vec![0, 1, 2, 8]));
+        assert_eq!(
+            fuzzy_match("SECTION", "section"),
+            Some(vec![0, 1, 2, 3, 4, 5, 6])
+        );
+        assert_eq!(fuzzy_match("", "anything"), Some(vec![]));
+        assert_eq!(fuzzy_match("2s", "Section 2"), None);
+        assert_eq!(fuzzy_match("11", "Section 1"), None);
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::{self, File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What happened to the file since the last poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Text was appended. `continuation` belongs to the last line seen so far,
+    /// which had no line terminator yet.
+    Appended {
+        continuation: Option<String>,
+        lines: Vec<String>,
+    },
+    /// The file was truncated or replaced (log rotation); these are its new contents.
+    Replaced(Vec<String>),
+}
+
+/// Watches a log file for appended data, like `tail -F`.
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    file: File,    // kept open so a rotated-away file's inode can't be reused
+    id: u64,       // inode of the file being followed
+    position: u64, // bytes consumed so far
+    ends_with_newline: bool,
+    last_poll: Instant,
+}
+
+impl Tail {
+    /// Starts following `path` from byte `position`, where the text read
+    /// from it so far ends, so nothing written since is missed.
+    pub fn new(path: impl Into<PathBuf>, position: u64) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        // A file that's shorter by now is picked up as truncated by the first poll.
+        let ends_with_newline = if position == 0 || position > metadata.len() {
+            true
+        } else {
+            let mut last = [0];
+            file.seek(SeekFrom::Start(position - 1))?;
+            file.read_exact(&mut last)?;
+            last[0] == b'\n'
+        };
+        Ok(Self {
+            path,
+            file,
+            id: file_id(&metadata),
+            position,
+            ends_with_newline,
+            last_poll: Instant::now(),
+        })
+    }
+
+    /// Bytes of the file read so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Checks the file for changes, at most every `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> std::io::Result<Option<Change>> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Ok(None);
+        }
+        self.last_poll = Instant::now();
+
+        // The file can briefly disappear while it is being rotated.
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(None);
+        };
+        if file_id(&metadata) != self.id || metadata.len() < self.position {
+            self.file = File::open(&self.path)?;
+            self.id = file_id(&self.file.metadata()?);
+            self.position = 0;
+            self.ends_with_newline = true;
+            let text = self.read_new()?;
+            return Ok(Some(Change::Replaced(split_lines(&text).collect())));
+        }
+        if metadata.len() == self.position {
+            return Ok(None);
+        }
+
+        let continues = !self.ends_with_newline;
+        let text = self.read_new()?;
+        let mut lines = split_lines(&text);
+        let continuation = if continues { lines.next() } else { None };
+        Ok(Some(Change::Appended {
+            continuation,
+            lines: lines.collect(),
+        }))
+    }
+
+    /// Whether the last line seen so far has no line terminator yet, so
+    /// more of it may still come.
+    pub fn last_line_open(&self) -> bool {
+        !self.ends_with_newline
+    }
+
+    /// Reads everything after `position`.
+    fn read_new(&mut self) -> std::io::Result<String> {
+        self.file.seek(SeekFrom::Start(self.position))?;
+        let mut bytes = Vec::new();
+        self.position += self.file.read_to_end(&mut bytes)? as u64;
+        if let Some(&last) = bytes.last() {
+            self.ends_with_newline = last == b'\n';
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Splits text into lines like `BufRead::lines`, without a trailing empty line.
+fn split_lines(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.strip_suffix('\n')
+        .unwrap_or(text)
+        .split('\n')
+        .filter(move |_| !text.is_empty())
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_owned())
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempLog;
+
+    /// Polls `tail` without waiting for `POLL_INTERVAL`.
+    fn poll(tail: &mut Tail) -> Option<Change> {
+        tail.last_poll -= POLL_INTERVAL;
+        tail.poll().unwrap()
+    }
+
+    fn appended(continuation: Option<&str>, lines: &[&str]) -> Option<Change> {
+        Some(Change::Appended {
+            continuation: continuation.map(str::to_owned),
+            lines: lines.iter().map(|&line| line.to_owned()).collect(),
+        })
+    }
+
+    #[test]
+    fn appended_lines_and_half_lines() {
+        let log = TempLog::new("tail-append.log", "one\n");
+        let mut tail = Tail::new(log.path(), 4).unwrap();
+        assert_eq!(poll(&mut tail), None);
+
+        log.append("two\r\nthr");
+        assert_eq!(poll(&mut tail), appended(None, &["two", "thr"]));
+        assert!(tail.last_line_open());
+        log.append("ee\nfour\n");
+        assert_eq!(poll(&mut tail), appended(Some("ee"), &["four"]));
+        assert!(!tail.last_line_open());
+        assert_eq!(tail.position(), 20);
+    }
+
+    #[test]
+    fn starts_in_the_middle_of_a_line() {
+        let log = TempLog::new("tail-start.log", "one\ntw");
+        let mut tail = Tail::new(log.path(), 6).unwrap();
+        assert!(tail.last_line_open());
+        log.append("o\n");
+        assert_eq!(poll(&mut tail), appended(Some("o"), &[]));
+    }
+
+    #[test]
+    fn truncation_replaces_the_lines() {
+        let log = TempLog::new("tail-truncate.log", "one\ntwo\n");
+        let mut tail = Tail::new(log.path(), 8).unwrap();
+        log.write("new\n");
+        assert_eq!(
+            poll(&mut tail),
+            Some(Change::Replaced(vec!["new".to_owned()]))
+        );
+        log.append("more\n");
+        assert_eq!(poll(&mut tail), appended(None, &["more"]));
+    }
+
+    #[test]
+    fn rotation_follows_the_new_file() {
+        let log = TempLog::new("tail-rotate.log", "one\n");
+        let rotated = TempLog::new("tail-rotate.log.1", "");
+        let mut tail = Tail::new(log.path(), 4).unwrap();
+        fs::rename(log.path(), rotated.path()).unwrap();
+        // Nothing to read while the file is gone.
+        assert_eq!(poll(&mut tail), None);
+
+        log.write("first\nsecond\n");
+        assert_eq!(
+            poll(&mut tail),
+            Some(Change::Replaced(vec![
+                "first".to_owned(),
+                "second".to_owned()
+            ]))
+        );
+        rotated.append("late\n");
+        log.append("third\n");
+        assert_eq!(poll(&mut tail), appended(None, &["third"]));
+    }
+}
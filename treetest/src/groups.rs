@@ -0,0 +1,102 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Tree identifier of the "Groups" node. Its children are identified by their
+/// index in `Groups::groups`.
+pub const GROUPS: usize = usize::MAX - 1;
+
+/// The lines sharing one value of the group pattern's `key` capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub key: String,
+    pub lines: Vec<usize>, // sorted
+}
+
+/// Log lines grouped by a correlation key such as a request or thread id,
+/// found with a regex's `key` capture. Lines without a key are in no group.
+#[derive(Debug, Clone)]
+pub struct Groups {
+    pub regex: Regex,
+    pub groups: Vec<Group>, // in order of first appearance
+    by_key: HashMap<String, usize>,
+}
+
+impl Groups {
+    /// Compiles a group pattern, which needs a capture named `key`.
+    pub fn regex(pattern: &str) -> Result<Regex, String> {
+        let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+        if !regex.capture_names().any(|name| name == Some("key")) {
+            return Err(format!("`{pattern}` has no capture named `key`"));
+        }
+        Ok(regex)
+    }
+
+    pub fn new(regex: Regex) -> Self {
+        Self {
+            regex,
+            groups: Vec::new(),
+            by_key: HashMap::new(),
+        }
+    }
+
+    /// The key of `line`, if it has one.
+    pub fn key<'a>(regex: &Regex, line: &'a str) -> Option<&'a str> {
+        Some(regex.captures(line)?.name("key")?.as_str())
+    }
+
+    /// Adds the lines among `lines` that have a key; the first has index `first`.
+    pub fn extend<S: AsRef<str>>(&mut self, lines: impl IntoIterator<Item = S>, first: usize) {
+        for (index, line) in lines.into_iter().enumerate() {
+            if let Some(key) = Self::key(&self.regex, line.as_ref()) {
+                let key = key.to_owned();
+                self.push(first + index, &key);
+            }
+        }
+    }
+
+    /// Adds `line`, whose key is `key`. Lines must be added in order.
+    pub fn push(&mut self, line: usize, key: &str) {
+        let index = match self.by_key.get(key) {
+            Some(&index) => index,
+            None => {
+                self.groups.push(Group {
+                    key: key.to_owned(),
+                    lines: Vec::new(),
+                });
+                self.by_key.insert(key.to_owned(), self.groups.len() - 1);
+                self.groups.len() - 1
+            }
+        };
+        self.groups[index].lines.push(line);
+    }
+
+    /// Starts over, e.g. when the file was replaced.
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.by_key.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_lines_by_key_in_order() {
+        let mut groups = Groups::new(Groups::regex(r"req=(?P<key>[0-9a-f]+)").unwrap());
+        groups.extend(["req=a1 start", "other", "req=b2 start", "req=a1 done"], 0);
+        groups.extend(["req=b2 done"], 4);
+        let found: Vec<_> = groups
+            .groups
+            .iter()
+            .map(|group| (group.key.as_str(), group.lines.clone()))
+            .collect();
+        assert_eq!(found, [("a1", vec![0, 3]), ("b2", vec![2, 4])]);
+    }
+
+    #[test]
+    fn needs_a_key_capture() {
+        assert!(Groups::regex(r"req=([0-9a-f]+)").is_err());
+        assert!(Groups::regex(r"req=(?P<key>").is_err());
+    }
+}
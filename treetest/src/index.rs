@@ -0,0 +1,171 @@
+use crate::store::CHECKPOINT_LINES;
+use crate::{Groups, Rules};
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+const BATCH_LINES: usize = 1 << 16;
+const READ_BUFFER: usize = 1 << 20;
+
+/// Lines indexed since the previous batch.
+#[derive(Debug, Default)]
+pub struct Batch {
+    pub first: usize,                         // index of the batch's first line
+    pub lines: usize,                         // number of lines in the batch
+    pub checkpoints: Vec<u64>,                // byte offset of every `CHECKPOINT_LINES`th line
+    pub end: u64,                             // byte offset just past the last line
+    pub headers: Vec<(usize, usize, String)>, // line index, depth and title of section headers
+    pub errors: Vec<usize>,                   // indices of lines matching the error pattern
+    pub keys: Vec<(usize, String)>,           // line index and group key of lines that have one
+    pub invalid: usize,                       // lines that aren't valid UTF-8
+    pub error: Option<std::io::Error>,        // why reading stopped early, in the last batch
+}
+
+/// Indexes a log file on a background thread: finds where lines start,
+/// and in the same pass, which lines are section headers or errors, and their
+/// group keys.
+#[derive(Debug)]
+pub struct Indexer {
+    receiver: Receiver<Batch>,
+    len: u64,                          // size of the file when indexing started
+    pub indexed: u64,                  // bytes indexed so far
+    pub invalid: usize,                // lines indexed so far that aren't valid UTF-8
+    pub error: Option<std::io::Error>, // why indexing stopped before the end, if it did
+    pub done: bool,                    // the whole file has been indexed
+}
+
+impl Indexer {
+    /// Indexes the `len` bytes of `file`; what's appended later is left to
+    /// follow mode.
+    pub fn spawn(file: File, len: u64, rules: Rules, error: Regex, group: Option<Regex>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::with_capacity(READ_BUFFER, file.take(len));
+            let mut batch = Batch::default();
+            let mut bytes = Vec::new();
+            let mut start = 0;
+            for index in 0.. {
+                bytes.clear();
+                let end = match reader.read_until(b'\n', &mut bytes) {
+                    Ok(0) => break,
+                    Ok(read) => start + read as u64,
+                    Err(err) => {
+                        batch.error = Some(err);
+                        break;
+                    }
+                };
+                let bytes = bytes.strip_suffix(b"\n").unwrap_or(&bytes);
+                let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+                let line = String::from_utf8_lossy(bytes);
+                if let std::borrow::Cow::Owned(_) = line {
+                    batch.invalid += 1;
+                }
+                if let Some((depth, title)) = rules.header(&line) {
+                    batch.headers.push((index, depth, title.to_owned()));
+                }
+                if error.is_match(&line) {
+                    batch.errors.push(index);
+                }
+                if let Some(key) = group.as_ref().and_then(|group| Groups::key(group, &line)) {
+                    batch.keys.push((index, key.to_owned()));
+                }
+                if index.is_multiple_of(CHECKPOINT_LINES) {
+                    batch.checkpoints.push(start);
+                }
+                batch.lines += 1;
+                batch.end = end;
+                start = end;
+
+                if batch.lines == BATCH_LINES {
+                    let next = Batch {
+                        first: index + 1,
+                        ..Batch::default()
+                    };
+                    // Stop once the viewer has gone away.
+                    if sender.send(std::mem::replace(&mut batch, next)).is_err() {
+                        return;
+                    }
+                }
+            }
+            let _ = sender.send(batch);
+        });
+        Self {
+            receiver,
+            len,
+            indexed: 0,
+            invalid: 0,
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Takes the batches indexed since the last call.
+    pub fn poll(&mut self) -> Vec<Batch> {
+        let mut batches = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => batches.push(self.count(batch)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        batches
+    }
+
+    /// Blocks until the whole file has been indexed, and takes the rest of the batches.
+    pub fn wait(&mut self) -> Vec<Batch> {
+        let batches = self.receiver.iter().collect::<Vec<_>>();
+        self.done = true;
+        batches.into_iter().map(|batch| self.count(batch)).collect()
+    }
+
+    fn count(&mut self, mut batch: Batch) -> Batch {
+        self.indexed = self.indexed.max(batch.end);
+        self.invalid += batch.invalid;
+        self.error = batch.error.take().or(self.error.take());
+        batch
+    }
+
+    /// How much of the file has been indexed, in percent.
+    pub fn progress(&self) -> u64 {
+        (self.indexed * 100).checked_div(self.len).unwrap_or(100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempLog;
+
+    #[test]
+    fn indexes_lines_headers_and_errors() {
+        let log = TempLog::new("index.log", b"+ a\r\nERROR x\n++ b\n\nlast");
+        let mut indexer = Indexer::spawn(
+            log.open(),
+            23,
+            Rules::default(),
+            Regex::new("ERROR").unwrap(),
+            Some(Regex::new("(?P<key>[ab])$").unwrap()),
+        );
+        let batches = indexer.wait();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(
+            (batch.lines, batch.checkpoints.as_slice()),
+            (5, [0].as_slice())
+        );
+        assert_eq!(batch.end, 23);
+        assert_eq!(
+            batch.headers,
+            [(0, 1, "a".to_owned()), (2, 2, "b".to_owned())]
+        );
+        assert_eq!(batch.errors, [1]);
+        assert_eq!(batch.keys, [(0, "a".to_owned()), (2, "b".to_owned())]);
+        assert_eq!(indexer.progress(), 100);
+    }
+}
@@ -1,76 +1,772 @@
 use clap::Parser;
-use crossterm::event::{Event, KeyCode, MouseEventKind};
+use crossterm::event::{Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use logfile::terminal::{init_terminal, restore_terminal};
+use logfile::LoadError;
 use ratatui::backend::Backend;
-use ratatui::layout::Position;
-use ratatui::prelude::{Color, Constraint, Layout, Line, Modifier, Style, Terminal, Text};
+use ratatui::layout::{Margin, Position, Rect};
+use ratatui::prelude::{Color, Constraint, Layout, Line, Modifier, Span, Style, Terminal, Text};
 
-use ratatui::widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation};
+use ratatui::widgets::{Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, Tabs};
 use ratatui::Frame;
+use regex::Regex;
+use std::io::{IsTerminal, Write};
 use std::time::{Duration, Instant};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
-use tui_tree_widget::{Tree, TreeItem, TreeState};
+use tui_tree_widget::Tree;
+
+mod bookmarks;
+mod diff;
+mod editor;
+mod error;
+mod error_lines;
+mod events;
+mod filter;
+mod follow;
+mod groups;
+mod index;
+mod minimap;
+mod outline;
+mod pipe;
+mod rules;
+mod search;
+mod session;
+mod settings;
+mod stats;
+mod store;
+mod stream;
+mod tab;
+pub use bookmarks::{Bookmark, Bookmarks};
+pub use diff::{diff, Comparison, DiffRow, Edit};
+pub use editor::editor_command;
+pub use error::AppError;
+pub use error_lines::ErrorLines;
+pub use events::{EventSource, ScriptedEvents, TerminalEvents};
+pub use filter::fuzzy_match;
+pub use follow::{Change, Tail};
+pub use groups::{Group, Groups};
+pub use minimap::{Minimap, Tick};
+pub use outline::{Outline, Section};
+pub use pipe::CommandOutput;
+pub use rules::{Depth, Preset, Rule, Rules};
+pub use search::{Match, Search};
+pub use session::Session;
+pub use settings::{state_dir, Settings};
+pub use stats::{Stat, Stats};
+pub use store::LineStore;
+pub use stream::Stream;
+pub use tab::Tab;
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about)]
 pub struct Config {
-    /// log file
-    #[arg(default_value = "treetest/testdata/dlog0.log")]
-    pub filename: String,
+    /// log files or glob patterns, each opened in its own tab; `-` reads stdin
+    /// [default: stdin if it's piped, otherwise treetest/testdata/dlog0.log]
+    pub filenames: Vec<String>,
+
+    /// follow the files as they grow, like `tail -f`
+    #[arg(short, long)]
+    pub follow: bool,
+
+    /// built-in section header convention; may be repeated [default: plus]
+    #[arg(short, long = "preset", value_enum)]
+    pub presets: Vec<Preset>,
+
+    /// section header regex with a `title` and optional `depth` capture; may be repeated
+    #[arg(short, long = "rule")]
+    pub rules: Vec<String>,
+
+    /// TOML file with section header rules
+    #[arg(long)]
+    pub rules_file: Option<String>,
+
+    /// regex for the lines `e` and `E` jump between
+    #[arg(long, default_value = "ERROR|FATAL|panicked")]
+    pub error_pattern: String,
+
+    /// regex with a `key` capture, e.g. a request or thread id; lines are
+    /// grouped by key in the tree
+    #[arg(short, long, value_name = "REGEX")]
+    pub group: Option<String>,
+
+    /// don't restore where each file was left the last time it was viewed
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// print a section, e.g. "Section 2/Section 2.1", with its subsections and exit
+    #[arg(long, value_name = "TITLES")]
+    pub extract: Option<String>,
+}
+
+impl Config {
+    /// Expands glob patterns in `filenames`. Names that match nothing are
+    /// kept as they are, so a missing file is still reported by name.
+    pub fn paths(&self) -> Vec<String> {
+        if self.filenames.is_empty() {
+            return vec![if std::io::stdin().is_terminal() {
+                "treetest/testdata/dlog0.log".to_owned()
+            } else {
+                logfile::STDIN.to_owned()
+            }];
+        }
+        self.filenames
+            .iter()
+            .flat_map(|pattern| {
+                if pattern == logfile::STDIN {
+                    return vec![pattern.clone()];
+                }
+                let matches: Vec<String> = glob::glob(pattern)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect();
+                if matches.is_empty() {
+                    vec![pattern.clone()]
+                } else {
+                    matches
+                }
+            })
+            .collect()
+    }
+
+    /// The section header rules, tried in order: `--rule` patterns, `--preset`s,
+    /// then the rules file. Without any, the `+` prefix convention is used.
+    pub fn section_rules(&self) -> std::io::Result<Rules> {
+        let mut rules = Rules::new(
+            self.rules
+                .iter()
+                .map(|pattern| Rule::from_pattern(pattern))
+                .collect::<std::io::Result<_>>()?,
+        );
+        rules.extend(Rules::from_presets(&self.presets));
+        if let Some(path) = &self.rules_file {
+            rules.extend(Rules::load(path)?);
+        }
+        Ok(if rules.is_empty() {
+            Rules::default()
+        } else {
+            rules
+        })
+    }
+
+    pub fn error_regex(&self) -> Result<Regex, AppError> {
+        Regex::new(&self.error_pattern).map_err(AppError::ErrorPattern)
+    }
+
+    pub fn group_regex(&self) -> Result<Option<Regex>, AppError> {
+        let pattern = self.group.as_deref();
+        pattern
+            .map(|pattern| Groups::regex(pattern).map_err(AppError::GroupPattern))
+            .transpose()
+    }
 }
 
+/// Writes the section `titles` (see `Outline::find`) of each log file to `out`.
+pub fn extract(config: &Config, titles: &str, out: &mut impl Write) -> Result<(), AppError> {
+    let rules = config.section_rules().map_err(AppError::Rules)?;
+    let error = config.error_regex()?;
+    for path in config.paths() {
+        let mut tab = Tab::new(&path, &rules, &error, None)?;
+        tab.finish_loading()
+            .map_err(|source| LoadError::read(&path, source))?;
+        let section = tab
+            .outline
+            .find(titles)
+            .ok_or_else(|| AppError::NoSection {
+                path: path.clone(),
+                section: titles.to_owned(),
+            })?;
+        tab::write_lines(out, tab.section_lines(section))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The pane that receives keyboard input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Tree,
+    Log,
+}
+
+/// What a key did to the prompt on the status line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKey {
+    Submitted, // Enter closed the prompt
+    Cancelled, // Esc closed the prompt
+    Edited,    // the text changed
+    Ignored,   // not a prompt key
+}
+
+/// Whether keys drive the panes or edit a prompt on the status line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Search,
+    Filter,
+    Export,
+    Note,
+    Pipe,
+}
+
+const MIN_NAV_WIDTH: u16 = 8;
+const MIN_LOG_WIDTH: u16 = 10;
+
 #[must_use]
 pub struct App {
-    pub filename: String, // name of the log file to view
-    pub state: TreeState<&'static str>,
-    items: Vec<TreeItem<'static, &'static str>>,
-    _lines: Vec<String>,
+    pub tabs: Vec<Tab>, // one per log file
+    pub current: usize, // index of the visible tab
+    pub focus: Focus,
+    nav_area: Rect, // pane areas from the last draw, for mouse hit-testing
+    log_area: Rect,
+    minimap_area: Rect,
+    pub mode: Mode,
+    prompt: String,                    // text typed at the status line prompt
+    search_origin: usize,              // log offset when the search prompt was opened
+    message: Option<String>,           // shown on the status line until the next key
+    pub settings: Settings,            // nav pane layout, saved when changed
+    dragging: bool,                    // the nav pane border is being dragged
+    pub output: Option<CommandOutput>, // of the last command the section was piped through
 }
 
 impl App {
-    pub fn new(config: &Config) -> Self {
-        let file = File::open(config.filename.clone()).expect("no such file");
-        let buf = BufReader::new(file);
-        let lines = buf
-            .lines()
-            .map(|l| l.expect("couldn't read the file lines"))
-            .collect();
-
+    pub fn new(config: &Config) -> Result<Self, AppError> {
+        let rules = config.section_rules().map_err(AppError::Rules)?;
+        let error = config.error_regex()?;
+        let group = config.group_regex()?;
         let mut app = Self {
-            filename: config.filename.to_owned(),
-            state: TreeState::default(),
-            items: vec![
-                TreeItem::new_leaf("Section 1", "Section 1"),
-                TreeItem::new(
-                    "Section 2",
-                    "Section 2",
-                    vec![
-                        TreeItem::new_leaf("Section 2.1", "Section 2.1"),
-                        TreeItem::new_leaf("Section 2.2", "Section 2.2"),
-                    ],
-                )
-                .expect("all item identifiers are unique"),
-                TreeItem::new_leaf("Section 3", "Section 3"),
-            ],
-            _lines: lines,
+            tabs: config
+                .paths()
+                .iter()
+                .map(|path| Tab::new(path, &rules, &error, group.as_ref()))
+                .collect::<Result<_, _>>()?,
+            current: 0,
+            focus: Focus::Tree,
+            nav_area: Rect::default(),
+            log_area: Rect::default(),
+            minimap_area: Rect::default(),
+            mode: Mode::Normal,
+            prompt: String::new(),
+            search_origin: 0,
+            message: None,
+            settings: Settings::load(),
+            dragging: false,
+            output: None,
         };
-        // Select the first item (if any)
-        if let Some(first) = app.items.first() {
-            app.state.select(vec![first.identifier()]);
+        if !config.fresh {
+            for tab in &mut app.tabs {
+                if let Some(session) = Session::load(&tab.filename) {
+                    tab.restore(session);
+                }
+            }
         }
-        app
+        app.message = app.tabs.iter().find_map(|tab| tab.warning.clone());
+        if config.follow {
+            for tab in &mut app.tabs {
+                app.message = tab.toggle_follow().or(app.message.take());
+            }
+        }
+        Ok(app)
+    }
+
+    pub fn tab(&self) -> &Tab {
+        &self.tabs[self.current]
+    }
+
+    pub fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.current]
+    }
+
+    pub fn next_tab(&mut self) -> bool {
+        self.current = (self.current + 1) % self.tabs.len();
+        true
+    }
+
+    pub fn previous_tab(&mut self) -> bool {
+        self.current = (self.current + self.tabs.len() - 1) % self.tabs.len();
+        true
+    }
+
+    pub fn toggle_follow(&mut self) -> bool {
+        self.message = self.tab_mut().toggle_follow();
+        true
+    }
+
+    /// Polls every followed file, including those in background tabs.
+    pub fn poll_follow(&mut self) -> bool {
+        let mut update = false;
+        for tab in &mut self.tabs {
+            update |= tab.poll_follow(&mut self.message);
+        }
+        update
+    }
+
+    /// Takes what the piped command printed since the last poll, and reports
+    /// why it failed once it's done.
+    pub fn poll_output(&mut self) -> bool {
+        let Some(output) = &mut self.output else {
+            return false;
+        };
+        let update = output.poll();
+        if !output.running {
+            if output.truncated {
+                self.message = Some(format!(
+                    "Stopped {} after {} lines",
+                    output.command,
+                    output.lines.len()
+                ));
+            } else if output.error.is_some() {
+                self.message = output.error.clone();
+            }
+        }
+        update
+    }
+
+    pub fn cycle_stat(&mut self) -> bool {
+        let tab = self.tab_mut();
+        tab.cycle_stat();
+        self.message = Some(format!("Tree shows {}", tab.stat.description()));
+        true
+    }
+
+    /// Jumps to the next (or previous) error line and reports its position.
+    pub fn jump_to_error(&mut self, forward: bool) -> bool {
+        let tab = self.tab_mut();
+        if forward {
+            tab.next_error();
+        } else {
+            tab.previous_error();
+        }
+        let errors = &self.tab().error_lines;
+        self.message = Some(match errors.current {
+            Some(current) => format!("error {} of {}", current + 1, errors.lines.len()),
+            None => format!("No lines match /{}/", errors.regex),
+        });
+        true
+    }
+
+    pub fn toggle_focus(&mut self) -> bool {
+        self.focus = match self.focus {
+            Focus::Tree => Focus::Log,
+            Focus::Log if self.settings.nav_hidden => Focus::Log,
+            Focus::Log => Focus::Tree,
+        };
+        true
+    }
+
+    /// Widens (or with a negative `delta`, narrows) the nav pane, showing it if hidden.
+    pub fn resize_nav(&mut self, delta: i16) -> bool {
+        self.settings.nav_hidden = false;
+        self.settings.nav_width = self
+            .settings
+            .nav_width
+            .saturating_add_signed(delta)
+            .max(MIN_NAV_WIDTH);
+        self.save_settings();
+        true
+    }
+
+    /// Hides or shows the nav pane.
+    pub fn toggle_nav(&mut self) -> bool {
+        self.settings.nav_hidden = !self.settings.nav_hidden;
+        if self.settings.nav_hidden {
+            self.focus = Focus::Log;
+        }
+        self.save_settings();
+        true
+    }
+
+    /// Saves where each log was left, for the next time it's opened.
+    pub fn save_sessions(&self) {
+        for tab in &self.tabs {
+            // Quitting goes ahead even if this fails; the session is only a convenience.
+            if let Some(session) = tab.session() {
+                let _ = session.save();
+            }
+        }
+    }
+
+    fn save_settings(&mut self) {
+        if let Err(err) = self.settings.save() {
+            self.message = Some(format!("Can't save the layout: {err}"));
+        }
+    }
+
+    fn mouse(&mut self, mouse: MouseEvent) -> bool {
+        let position = Position::new(mouse.column, mouse.row);
+        let over_log = self.log_area.contains(position);
+        let over_nav = self.nav_area.contains(position);
+        let over_minimap = self.minimap_area.contains(position);
+        // The right border of the nav pane can be dragged to resize it.
+        let on_border = over_nav && mouse.column + 1 == self.nav_area.right();
+        let tab = &mut self.tabs[self.current];
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_border => {
+                self.dragging = true;
+                false
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => {
+                let width = (mouse.column + 1).saturating_sub(self.nav_area.x);
+                self.settings.nav_width = width.max(MIN_NAV_WIDTH);
+                true
+            }
+            MouseEventKind::Up(MouseButton::Left) if self.dragging => {
+                self.dragging = false;
+                self.save_settings();
+                true
+            }
+            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left)
+                if over_minimap =>
+            {
+                let rows = self.minimap_area.height as usize;
+                let row = (mouse.row - self.minimap_area.y) as usize;
+                let len = tab.lines.len();
+                let line = minimap::row_lines(row, rows, len).start;
+                len > 0 && tab.show_line(line.min(len - 1))
+            }
+            MouseEventKind::ScrollDown if over_log && self.output.is_some() => self
+                .output
+                .as_mut()
+                .is_some_and(|output| output.scroll_down(3)),
+            MouseEventKind::ScrollUp if over_log && self.output.is_some() => self
+                .output
+                .as_mut()
+                .is_some_and(|output| output.scroll_up(3)),
+            MouseEventKind::ScrollDown if over_log => tab.scroll_log_down(3),
+            MouseEventKind::ScrollUp if over_log => tab.scroll_log_up(3),
+            MouseEventKind::ScrollDown => tab.state.scroll_down(1),
+            MouseEventKind::ScrollUp => tab.state.scroll_up(1),
+            MouseEventKind::Down(_button) if over_log => {
+                self.focus = Focus::Log;
+                // The pane's top border is above the first line.
+                let row = (mouse.row - self.log_area.y).checked_sub(1);
+                if let Some(line) = row.and_then(|row| tab.shown_lines().get(row as usize).copied())
+                {
+                    tab.cursor = line;
+                }
+                true
+            }
+            MouseEventKind::Down(_button) if over_nav => {
+                self.focus = Focus::Tree;
+                let selected = tab.state.selected().to_vec();
+                tab.state.click_at(position);
+                if tab.state.selected() != selected {
+                    tab.scroll_to_selected();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn start_search(&mut self) -> bool {
+        self.mode = Mode::Search;
+        self.prompt.clear();
+        self.search_origin = self.tab().log_offset;
+        true
+    }
+
+    /// Handles a key while the search prompt is open, searching as the user types.
+    fn search_key(&mut self, code: KeyCode) -> bool {
+        match self.edit_prompt(code) {
+            PromptKey::Submitted => {
+                if self.prompt.is_empty() {
+                    self.tab_mut().set_search(None);
+                }
+            }
+            PromptKey::Cancelled => {
+                let origin = self.search_origin;
+                let tab = self.tab_mut();
+                tab.set_search(None);
+                tab.log_offset = origin;
+            }
+            PromptKey::Edited => {
+                let tab = &mut self.tabs[self.current];
+                tab.search_for(&self.prompt, self.search_origin);
+            }
+            PromptKey::Ignored => return false,
+        }
+        true
+    }
+
+    /// Edits the prompt with `code`: characters and Backspace change it, and
+    /// Enter and Esc close it.
+    fn edit_prompt(&mut self, code: KeyCode) -> PromptKey {
+        match code {
+            KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                PromptKey::Submitted
+            }
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                PromptKey::Cancelled
+            }
+            KeyCode::Backspace => {
+                self.prompt.pop();
+                PromptKey::Edited
+            }
+            KeyCode::Char(c) => {
+                self.prompt.push(c);
+                PromptKey::Edited
+            }
+            _ => PromptKey::Ignored,
+        }
+    }
+
+    /// Opens the tree filter prompt.
+    pub fn start_filter(&mut self) -> bool {
+        self.settings.nav_hidden = false;
+        self.mode = Mode::Filter;
+        self.focus = Focus::Tree;
+        self.prompt.clear();
+        self.tab_mut().start_filter();
+        true
+    }
+
+    /// Opens the prompt for where to export the selected section.
+    pub fn start_export(&mut self) -> bool {
+        if self.tab().selected_section().is_none() {
+            return false;
+        }
+        self.mode = Mode::Export;
+        self.prompt.clear();
+        true
+    }
+
+    /// Handles a key while the export prompt is open; Enter writes the file.
+    fn export_key(&mut self, code: KeyCode) -> bool {
+        match self.edit_prompt(code) {
+            PromptKey::Submitted if !self.prompt.is_empty() => {
+                self.message = Some(match self.tab().export_selected(&self.prompt) {
+                    Ok(count) => format!("Wrote {count} lines to {}", self.prompt),
+                    Err(err) => format!("Can't write {}: {err}", self.prompt),
+                });
+            }
+            PromptKey::Ignored => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Suspends the UI to open the current line in the user's editor, then
+    /// reloads the file.
+    pub fn edit<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> std::io::Result<bool> {
+        let (path, line) = match self.tab().edit_target() {
+            Ok((path, line)) => (path.to_owned(), line),
+            Err(message) => {
+                self.message = Some(message);
+                return Ok(true);
+            }
+        };
+        let mut command = editor_command(&path, line);
+        restore_terminal()?;
+        let status = command.status();
+        init_terminal()?;
+        terminal.clear()?;
+
+        let status = match status {
+            Ok(status) => status,
+            Err(err) => {
+                let program = command.get_program().to_string_lossy();
+                self.message = Some(format!("Can't run {program}: {err}"));
+                return Ok(true);
+            }
+        };
+        // Whatever was saved is shown, even if the editor failed.
+        self.message = match self.tab_mut().reload() {
+            Err(err) => Some(err.to_string()),
+            Ok(()) if !status.success() => Some(format!("The editor exited with {status}")),
+            Ok(()) => self.tab().warning.clone(),
+        };
+        Ok(true)
+    }
+
+    /// Opens the prompt for a shell command to pipe the selected section through.
+    pub fn start_pipe(&mut self) -> bool {
+        if self.tab().selected_section().is_none() {
+            self.message = Some("Select a section to pipe".to_owned());
+            return true;
+        }
+        self.mode = Mode::Pipe;
+        self.prompt.clear();
+        true
+    }
+
+    /// Handles a key while the pipe prompt is open; Enter runs the command
+    /// and shows its output.
+    fn pipe_key(&mut self, code: KeyCode) -> bool {
+        match self.edit_prompt(code) {
+            PromptKey::Submitted if !self.prompt.trim().is_empty() => {
+                let tab = self.tab();
+                let Some(id) = tab.selected_section_id() else {
+                    return true;
+                };
+                let output = tab
+                    .section_store(id)
+                    .and_then(|input| CommandOutput::run(&self.prompt, input));
+                match output {
+                    Ok(output) => self.output = Some(output),
+                    Err(err) => self.message = Some(format!("Can't run {}: {err}", self.prompt)),
+                }
+            }
+            PromptKey::Ignored => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Handles a key for the command output: scrolling, Enter to show it in
+    /// the log pane, and Esc to close it, stopping the command if it's still
+    /// running.
+    fn output_key(&mut self, code: KeyCode) -> bool {
+        let Some(output) = &mut self.output else {
+            return false;
+        };
+        let page = output.height.saturating_sub(1).max(1);
+        match code {
+            KeyCode::Down => output.scroll_down(1),
+            KeyCode::Up => output.scroll_up(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => output.scroll_down(page),
+            KeyCode::PageUp => output.scroll_up(page),
+            KeyCode::Home => output.scroll_up(output.offset),
+            KeyCode::End => output.scroll_down(output.lines.len()),
+            KeyCode::Enter if !output.docked => {
+                output.docked = true;
+                self.focus = Focus::Log;
+                true
+            }
+            KeyCode::Esc => {
+                self.output = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Marks the selected section for comparing, or compares the marked
+    /// section with it side by side in the log pane.
+    pub fn compare(&mut self) -> bool {
+        self.message = Some(self.tab_mut().compare());
+        if self.tab().comparison.is_some() {
+            self.focus = Focus::Log;
+        }
+        true
+    }
+
+    /// Bookmarks the current line, or removes its bookmark.
+    pub fn toggle_bookmark(&mut self) -> bool {
+        self.message = Some(self.tab_mut().toggle_bookmark());
+        true
+    }
+
+    /// Opens the prompt for the current line's note, filled in with the note it has.
+    pub fn start_note(&mut self) -> bool {
+        self.mode = Mode::Note;
+        self.prompt = self.tab().current_note().unwrap_or_default().to_owned();
+        true
+    }
+
+    /// Handles a key while the note prompt is open; Enter saves the note.
+    fn note_key(&mut self, code: KeyCode) -> bool {
+        match self.edit_prompt(code) {
+            PromptKey::Submitted => {
+                let prompt = std::mem::take(&mut self.prompt);
+                self.message = self.tab_mut().set_note(&prompt);
+            }
+            PromptKey::Ignored => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Handles a key while the filter prompt is open, narrowing the tree as the user types.
+    fn filter_key(&mut self, code: KeyCode) -> bool {
+        let selected = self.tab().state.selected().to_vec();
+        match code {
+            KeyCode::Up => {
+                self.tab_mut().state.key_up();
+            }
+            KeyCode::Down => {
+                self.tab_mut().state.key_down();
+            }
+            _ => match self.edit_prompt(code) {
+                PromptKey::Submitted | PromptKey::Cancelled => {
+                    self.tab_mut().end_filter(code == KeyCode::Enter);
+                    return true;
+                }
+                PromptKey::Edited => {
+                    let tab = &mut self.tabs[self.current];
+                    tab.apply_filter(&self.prompt);
+                }
+                PromptKey::Ignored => return false,
+            },
+        }
+        let tab = self.tab_mut();
+        if tab.state.selected() != selected {
+            tab.scroll_to_selected();
+        }
+        true
     }
 
     fn draw(&mut self, frame: &mut Frame) {
-        let horizontal = Layout::horizontal([Constraint::Length(20), Constraint::Min(1)]);
-        let [nav_area, log_area] = horizontal.areas(frame.size());
+        let tab_bar_height = if self.tabs.len() > 1 { 1 } else { 0 };
+        let vertical = Layout::vertical([
+            Constraint::Length(tab_bar_height),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ]);
+        let [tab_bar_area, main_area, status_area] = vertical.areas(frame.size());
+        let nav_width = if self.settings.nav_hidden {
+            0
+        } else {
+            let widest = main_area.width.saturating_sub(MIN_LOG_WIDTH);
+            self.settings.nav_width.min(widest)
+        };
+        let horizontal = Layout::horizontal([
+            Constraint::Length(nav_width),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ]);
+        let [nav_area, log_area, minimap_area] = horizontal.areas(main_area);
+        // The minimap lines up with the log pane inside its borders.
+        let minimap_area = minimap_area.inner(&Margin::new(0, 1));
+        self.nav_area = nav_area;
+        self.log_area = log_area;
+        self.minimap_area = minimap_area;
+        let border = |focus| {
+            if self.focus == focus {
+                Style::new().fg(Color::LightGreen)
+            } else {
+                Style::new()
+            }
+        };
+        let (tree_border, log_border) = (border(Focus::Tree), border(Focus::Log));
+
+        if self.tabs.len() > 1 {
+            let titles = self.tabs.iter().map(Tab::name);
+            let tab_bar = Tabs::new(titles).select(self.current).highlight_style(
+                Style::new()
+                    .fg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            );
+            frame.render_widget(tab_bar, tab_bar_area);
+        }
+        frame.render_widget(Paragraph::new(self.status()), status_area);
+        if let Some(prefix) = self.prompt_prefix() {
+            let width = prefix.chars().count() + self.prompt.chars().count();
+            let x = status_area.x + width as u16;
+            frame.set_cursor(x.min(status_area.right().saturating_sub(1)), status_area.y);
+        }
+
+        let tab = &mut self.tabs[self.current];
+        tab.log_height = log_area.height.saturating_sub(2) as usize;
+        tab.stick_to_bottom();
 
-        let tree = Tree::new(&self.items)
-            .expect("all item identifiers are unique")
+        let tree = Tree::new(&tab.items)
+            .expect("section indices are unique")
             .block(
-                Block::bordered().title("Table of Contents"), // .title_bottom(format!("{:?}", self.state)),
+                Block::bordered()
+                    .title("Table of Contents")
+                    .border_style(tree_border), // .title_bottom(format!("{:?}", self.state)),
             )
             .experimental_scrollbar(Some(
                 Scrollbar::new(ScrollbarOrientation::VerticalRight)
@@ -85,15 +781,248 @@ impl App {
                     .add_modifier(Modifier::BOLD),
             );
 
-        let lines: Vec<Line> = self._lines.clone().into_iter().map(Line::from).collect();
-        let log = Paragraph::new(Text::from(lines)).block(Block::bordered().title("Log Lines"));
+        // Only materialize the lines that fit in the pane.
+        let selected = tab
+            .selected_section()
+            .map_or(0..0, |section| section.start..section.end);
+        let shown = tab.shown_lines();
+        let cursor = (self.focus == Focus::Log).then(|| tab.current_line());
+        let texts: Vec<_> = shown.iter().map(|&index| tab.lines.get(index)).collect();
+        let lines: Vec<Line> = shown
+            .iter()
+            .copied()
+            .zip(&texts)
+            .map(|(index, line)| {
+                let line = match &tab.search {
+                    Some(search) => highlight_matches(
+                        line,
+                        search.in_lines(index..index + 1),
+                        search.current_match(),
+                    ),
+                    None => Line::from(line.as_ref()),
+                };
+                let line = if index == selected.start {
+                    line.style(
+                        Style::new()
+                            .bg(Color::DarkGray)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else if selected.contains(&index) {
+                    line.style(Style::new().bg(Color::DarkGray))
+                } else {
+                    line
+                };
+                let line = if tab.error_lines.contains(index) {
+                    line.patch_style(Style::new().fg(Color::LightRed))
+                } else {
+                    line
+                };
+                let line = if tab.bookmarks.contains(index) {
+                    line.patch_style(Style::new().add_modifier(Modifier::UNDERLINED))
+                } else {
+                    line
+                };
+                if cursor == Some(index) {
+                    line.patch_style(Style::new().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        let title = match tab.selected_group() {
+            Some(group) => format!("Lines with key {}", group.key),
+            None => "Log Lines".to_owned(),
+        };
+        let log = Paragraph::new(Text::from(lines))
+            .block(Block::bordered().title(title).border_style(log_border));
+        let comparison = tab.comparison.as_ref().map(|comparison| {
+            let [left_text, right_text] = comparison_text(&tab.lines, comparison, tab.log_height);
+            let [left, right] = [comparison.left, comparison.right]
+                .map(|section| format!("= {}", tab.outline.sections[section].title));
+            [
+                Paragraph::new(left_text)
+                    .block(Block::bordered().title(left).border_style(log_border)),
+                Paragraph::new(right_text).block(
+                    Block::bordered()
+                        .title(right)
+                        .title_bottom("Esc: back to the log")
+                        .border_style(log_border),
+                ),
+            ]
+        });
+        // The log pane shows the comparison, if there is one, in two halves.
+        let render_log = |frame: &mut Frame| match comparison {
+            Some(panes) => {
+                let halves: [Rect; 2] =
+                    Layout::horizontal([Constraint::Percentage(50); 2]).areas(log_area);
+                for (pane, area) in panes.into_iter().zip(halves) {
+                    frame.render_widget(pane, area);
+                }
+            }
+            None => frame.render_widget(log, log_area),
+        };
+
+        if !nav_area.is_empty() {
+            frame.render_stateful_widget(tree, nav_area, &mut tab.state);
+        }
+        frame.render_widget(Minimap { tab }, minimap_area);
+        match &mut self.output {
+            Some(output) if output.docked => {
+                output.height = log_area.height.saturating_sub(2) as usize;
+                let block = Block::bordered()
+                    .title(output_title(output))
+                    .title_bottom("Esc: back to the log")
+                    .border_style(log_border);
+                frame.render_widget(Paragraph::new(output_text(output)).block(block), log_area);
+            }
+            Some(output) => {
+                render_log(frame);
+                let area = log_area.inner(&Margin::new(2, 1));
+                output.height = area.height.saturating_sub(2) as usize;
+                let block = Block::bordered()
+                    .title(output_title(output))
+                    .title_bottom("Enter: show in the log pane  Esc: close")
+                    .border_style(Style::new().fg(Color::LightGreen));
+                frame.render_widget(Clear, area);
+                frame.render_widget(Paragraph::new(output_text(output)).block(block), area);
+            }
+            None => render_log(frame),
+        }
+    }
+
+    fn prompt_prefix(&self) -> Option<&'static str> {
+        match self.mode {
+            Mode::Normal => None,
+            Mode::Search => Some("/"),
+            Mode::Filter => Some("filter: "),
+            Mode::Export => Some("export section to: "),
+            Mode::Note => Some("note: "),
+            Mode::Pipe => Some("| "),
+        }
+    }
 
-        frame.render_stateful_widget(tree, nav_area, &mut self.state);
-        frame.render_widget(log, log_area);
+    fn status(&self) -> Line<'_> {
+        if let Some(prefix) = self.prompt_prefix() {
+            return Line::from(format!("{prefix}{}", self.prompt));
+        }
+        if let Some(message) = &self.message {
+            return Line::from(message.as_str()).style(Style::new().fg(Color::Yellow));
+        }
+        let mut status = self.search_status();
+        if let Some(index) = &self.tab().index {
+            status.spans.push(Span::styled(
+                format!("  [indexing {}%]", index.progress()),
+                Style::new().fg(Color::LightGreen),
+            ));
+        }
+        if self.tab().stream.is_some() {
+            status.spans.push(Span::styled(
+                "  [reading stdin]",
+                Style::new().fg(Color::LightGreen),
+            ));
+        }
+        if self.tab().follow.is_some() {
+            status.spans.push(Span::styled(
+                "  [following]",
+                Style::new().fg(Color::LightGreen),
+            ));
+        }
+        status
+    }
+
+    fn search_status(&self) -> Line<'_> {
+        let tab = self.tab();
+        let done = |search: &Search| search.is_done(tab.lines.len());
+        match &tab.search {
+            Some(search) if search.matches.is_empty() && done(search) => {
+                Line::from(format!("Pattern not found: {}", search.query))
+                    .style(Style::new().fg(Color::Red))
+            }
+            Some(search) => Line::from(format!(
+                "/{}  match {} of {}{}",
+                search.query,
+                search.current.map_or(0, |current| current + 1),
+                search.matches.len(),
+                if search.is_full() {
+                    "+"
+                } else if !done(search) {
+                    " (searching)"
+                } else {
+                    ""
+                }
+            )),
+            None => Line::from(tab.name()),
+        }
+    }
+}
+
+fn output_title(output: &CommandOutput) -> String {
+    if output.running {
+        format!("| {} (running)", output.command)
+    } else {
+        format!("| {}", output.command)
+    }
+}
+
+/// The lines of `output` that fit in its pane.
+fn output_text(output: &CommandOutput) -> Text<'_> {
+    let lines = output.lines.iter().skip(output.offset).take(output.height);
+    Text::from_iter(lines.map(String::as_str))
+}
+
+/// The two sides of the `height` rows of `comparison` that fit in the log
+/// pane, with the lines that differ colored by how.
+fn comparison_text<'a>(
+    lines: &'a LineStore,
+    comparison: &Comparison,
+    height: usize,
+) -> [Text<'a>; 2] {
+    let mut sides = [Vec::new(), Vec::new()];
+    for row in comparison.rows.iter().skip(comparison.offset).take(height) {
+        let style = match row.edit {
+            Edit::Same => Style::new(),
+            Edit::Changed => Style::new().fg(Color::Yellow),
+            Edit::Removed => Style::new().fg(Color::LightRed),
+            Edit::Added => Style::new().fg(Color::LightGreen),
+        };
+        for (side, index) in sides.iter_mut().zip([row.left, row.right]) {
+            side.push(
+                index.map_or_else(Line::default, |index| Line::styled(lines.get(index), style)),
+            );
+        }
+    }
+    sides.map(Text::from)
+}
+
+/// Splits `line` into spans so the search matches on it stand out.
+fn highlight_matches<'a>(line: &'a str, matches: &[Match], current: Option<&Match>) -> Line<'a> {
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for m in matches {
+        // Matches overlapping the previous one are skipped, and so are those
+        // that no longer fit the line, found before the file was rewritten.
+        let (Some(before), Some(text)) = (line.get(end..m.bytes.start), line.get(m.bytes.clone()))
+        else {
+            continue;
+        };
+        spans.push(Span::raw(before));
+        let style = if Some(m) == current {
+            Style::new().fg(Color::Black).bg(Color::LightRed)
+        } else {
+            Style::new().fg(Color::Black).bg(Color::Yellow)
+        };
+        spans.push(Span::styled(text, style));
+        end = m.bytes.end;
     }
+    spans.push(Span::raw(&line[end..]));
+    Line::from(spans)
 }
 
-pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> std::io::Result<()> {
+pub fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    events: &mut impl EventSource,
+) -> std::io::Result<()> {
     const DEBOUNCE: Duration = Duration::from_millis(20); // 50 FPS
 
     terminal.draw(|frame| app.draw(frame))?;
@@ -102,41 +1031,54 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> std::io:
 
     loop {
         let timeout = debounce.map_or(DEBOUNCE, |start| DEBOUNCE.saturating_sub(start.elapsed()));
-        if crossterm::event::poll(timeout)? {
-            let update = match crossterm::event::read()? {
+        if let Some(event) = events.next(timeout)? {
+            if let Event::Key(_) = event {
+                app.message = None;
+            }
+            let update = match event {
+                Event::Key(key) if app.mode == Mode::Search => app.search_key(key.code),
+                Event::Key(key) if app.mode == Mode::Filter => app.filter_key(key.code),
+                Event::Key(key) if app.mode == Mode::Export => app.export_key(key.code),
+                Event::Key(key) if app.mode == Mode::Note => app.note_key(key.code),
+                Event::Key(key) if app.mode == Mode::Pipe => app.pipe_key(key.code),
+                // The output overlay takes all keys until it's docked or closed.
+                Event::Key(key) if app.output.as_ref().is_some_and(|output| !output.docked) => {
+                    app.output_key(key.code)
+                }
                 Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('\n' | ' ') => app.state.toggle_selected(),
-                    KeyCode::Left => {
-                        // Always want there to be a selection, so don't do anything
-                        // if a first-level item is selected and it's not opened.
-                        if app.state.selected().len() == 1
-                            && !app.state.opened().contains(app.state.selected())
-                        {
-                            false
-                        } else {
-                            app.state.key_left()
-                        }
-                    }
-
-                    KeyCode::Right => app.state.key_right(),
-                    KeyCode::Down => app.state.key_down(),
-                    KeyCode::Up => app.state.key_up(),
-                    KeyCode::Esc => app.state.select_first(),
-                    KeyCode::Home => app.state.select_first(),
-                    KeyCode::End => app.state.select_last(),
-                    KeyCode::PageDown => app.state.scroll_down(3),
-                    KeyCode::PageUp => app.state.scroll_up(3),
-                    _ => false,
-                },
-                Event::Mouse(mouse) => match mouse.kind {
-                    MouseEventKind::ScrollDown => app.state.scroll_down(1),
-                    MouseEventKind::ScrollUp => app.state.scroll_up(1),
-                    MouseEventKind::Down(_button) => {
-                        app.state.click_at(Position::new(mouse.column, mouse.row))
+                    KeyCode::Char('q') => {
+                        app.save_sessions();
+                        return Ok(());
                     }
-                    _ => false,
+                    KeyCode::Tab | KeyCode::BackTab => app.toggle_focus(),
+                    KeyCode::Char('/') => app.start_search(),
+                    KeyCode::Char('f') => app.start_filter(),
+                    KeyCode::Char('n') => app.tab_mut().next_match(),
+                    KeyCode::Char('N') => app.tab_mut().previous_match(),
+                    KeyCode::Char('F') => app.toggle_follow(),
+                    KeyCode::Char('x') => app.start_export(),
+                    KeyCode::Char('s') => app.cycle_stat(),
+                    KeyCode::Char('e') => app.jump_to_error(true),
+                    KeyCode::Char('E') => app.jump_to_error(false),
+                    KeyCode::Char('m') => app.toggle_bookmark(),
+                    KeyCode::Char('M') => app.start_note(),
+                    KeyCode::Char('b') => app.tab_mut().next_bookmark(),
+                    KeyCode::Char('B') => app.tab_mut().previous_bookmark(),
+                    KeyCode::Char('v') => app.edit(terminal)?,
+                    KeyCode::Char('|') => app.start_pipe(),
+                    KeyCode::Char('=') => app.compare(),
+                    KeyCode::Char(']') => app.next_tab(),
+                    KeyCode::Char('[') => app.previous_tab(),
+                    KeyCode::Char('>') => app.resize_nav(2),
+                    KeyCode::Char('<') => app.resize_nav(-2),
+                    KeyCode::Char('t') => app.toggle_nav(),
+                    code => match app.focus {
+                        Focus::Tree => app.tab_mut().tree_key(code),
+                        Focus::Log if app.output.is_some() => app.output_key(code),
+                        Focus::Log => app.tab_mut().log_key(code),
+                    },
                 },
+                Event::Mouse(mouse) => app.mouse(mouse),
                 Event::Resize(_, _) => true,
                 _ => false,
             };
@@ -144,12 +1086,543 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> std::io:
                 debounce.get_or_insert_with(Instant::now);
             }
         }
-        if debounce.is_some_and(|debounce| debounce.elapsed() > DEBOUNCE) {
+        if app.poll_follow() | app.poll_output() | app.tab_mut().search_more() {
+            debounce.get_or_insert_with(Instant::now);
+        }
+        // A scripted run waits for a piped command, as someone watching would.
+        let running = app.output.as_ref().is_some_and(|output| output.running);
+        let finished = events.finished() && !running;
+        if debounce.is_some_and(|debounce| finished || debounce.elapsed() > DEBOUNCE) {
             terminal.draw(|frame| {
                 app.draw(frame);
             })?;
 
             debounce = None;
         }
+        if finished {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyEvent, KeyModifiers};
+    use ratatui::backend::TestBackend;
+    use ratatui::buffer::Buffer;
+    use std::path::PathBuf;
+
+    /// A log file in the temp directory for tests that change or follow
+    /// their log. It's deleted when dropped, along with notes saved next to it.
+    pub(crate) struct TempLog(PathBuf);
+
+    impl TempLog {
+        /// A temp file named after `name`, which is unique per test, holding `text`.
+        pub(crate) fn new(name: &str, text: impl AsRef<[u8]>) -> Self {
+            let file = format!("treetest-{}-{name}", std::process::id());
+            let log = Self(std::env::temp_dir().join(file));
+            std::fs::write(&log.0, text).unwrap();
+            log
+        }
+
+        /// A temp copy of the test log.
+        pub(crate) fn copy(name: &str) -> Self {
+            Self::new(name, std::fs::read("testdata/dlog0.log").unwrap())
+        }
+
+        pub(crate) fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+
+        pub(crate) fn write(&self, text: impl AsRef<[u8]>) {
+            std::fs::write(&self.0, text).unwrap();
+        }
+
+        pub(crate) fn append(&self, text: impl AsRef<[u8]>) {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&self.0)
+                .unwrap();
+            file.write_all(text.as_ref()).unwrap();
+        }
+
+        pub(crate) fn open(&self) -> std::fs::File {
+            std::fs::File::open(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+            let _ = std::fs::remove_file(format!("{}.notes.toml", self.path()));
+        }
+    }
+
+    fn app(args: &[&str]) -> App {
+        let config = Config::parse_from(["treetest", "--fresh"].iter().chain(args));
+        let mut app = App::new(&config).unwrap();
+        app.settings = Settings::default();
+        for tab in &mut app.tabs {
+            tab.finish_loading().unwrap();
+        }
+        app
+    }
+
+    /// Runs the viewer on the test log through `events` and returns its last frame.
+    fn render(events: impl IntoIterator<Item = Event>) -> Buffer {
+        render_file("testdata/dlog0.log", events)
+    }
+
+    fn render_file(path: &str, events: impl IntoIterator<Item = Event>) -> Buffer {
+        render_args(&[path], events)
+    }
+
+    fn render_args(args: &[&str], events: impl IntoIterator<Item = Event>) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(60, 12)).unwrap();
+        run_app(&mut terminal, app(args), &mut ScriptedEvents::new(events)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect()
+    }
+
+    /// The rows of the table of contents pane, which is 20 columns wide.
+    fn nav_rows(buffer: &Buffer) -> Vec<String> {
+        rows(buffer)
+            .iter()
+            .map(|row| row.chars().take(20).collect())
+            .collect()
+    }
+
+    /// The text of the highlighted tree item.
+    fn selected(buffer: &Buffer) -> String {
+        let text: String = buffer
+            .content
+            .iter()
+            .filter(|cell| cell.bg == Color::LightGreen)
+            .map(|cell| cell.symbol())
+            .collect();
+        text.trim().to_owned()
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::from(code))
+    }
+
+    fn click(column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    #[test]
+    fn compressed_files_are_read_in_the_background() {
+        let gzip = std::process::Command::new("gzip")
+            .args(["-c", "testdata/dlog0.log"])
+            .output()
+            .unwrap();
+        let log = TempLog::new("compressed.log.gz", gzip.stdout);
+        let config = Config::parse_from(["treetest", "--fresh", log.path()]);
+        let mut compressed = App::new(&config).unwrap();
+        let tab = compressed.tab_mut();
+        assert!(tab.stream.is_some());
+        tab.finish_loading().unwrap();
+
+        let plain = app(&["testdata/dlog0.log"]);
+        assert_eq!(
+            compressed.tab().lines.iter().collect::<Vec<_>>(),
+            plain.tab().lines.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            compressed.tab().outline.sections.len(),
+            plain.tab().outline.sections.len()
+        );
+    }
+
+    /// Polls `tab` until `done` holds for it, failing after a few seconds.
+    fn poll_until(tab: &mut Tab, message: &mut Option<String>, done: impl Fn(&Tab) -> bool) {
+        let start = Instant::now();
+        while !done(tab) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            tab.poll_follow(message);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn streamed_sections_appear_as_their_headers_arrive() {
+        let fifo = TempLog::new("stream.fifo", "");
+        std::fs::remove_file(fifo.path()).unwrap();
+        let made = std::process::Command::new("mkfifo")
+            .arg(fifo.path())
+            .status();
+        assert!(made.unwrap().success());
+        // Opened for reading too, so it doesn't wait for the tab to open it.
+        let mut writer = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(fifo.path())
+            .unwrap();
+        let error = Regex::new("ERROR").unwrap();
+        let mut tab = Tab::new(fifo.path(), &Rules::default(), &error, None).unwrap();
+        let titles = |tab: &Tab| -> Vec<String> {
+            let sections = tab.outline.sections.iter();
+            sections.map(|section| section.title.clone()).collect()
+        };
+        let mut message = None;
+
+        writer.write_all(b"+ A\none\n").unwrap();
+        poll_until(&mut tab, &mut message, |tab| tab.lines.len() == 2);
+        assert_eq!(titles(&tab), ["A"]);
+        writer.write_all(b"+ B\ntwo \xff\n").unwrap();
+        poll_until(&mut tab, &mut message, |tab| tab.lines.len() == 4);
+        assert_eq!(titles(&tab), ["A", "B"]);
+        assert!(tab.stream.as_ref().is_some_and(|stream| !stream.closed));
+        assert_eq!(message, None);
+
+        drop(writer);
+        poll_until(&mut tab, &mut message, |tab| tab.stream.is_none());
+        let message = message.unwrap();
+        assert!(message.contains("1 invalid UTF-8 line(s)"), "{message}");
+    }
+
+    #[test]
+    fn streams_that_fail_say_where_they_stopped() {
+        let gzip = std::process::Command::new("gzip")
+            .args(["-c", "testdata/dlog0.log"])
+            .output()
+            .unwrap();
+        let cut = &gzip.stdout[..gzip.stdout.len() / 2];
+        let log = TempLog::new("cut.log.gz", cut);
+        let error = Regex::new("ERROR").unwrap();
+        let mut tab = Tab::new(log.path(), &Rules::default(), &error, None).unwrap();
+        let mut message = None;
+        poll_until(&mut tab, &mut message, |tab| tab.stream.is_none());
+        let message = message.unwrap();
+        assert!(message.starts_with("Stopped reading"), "{message}");
+    }
+
+    #[test]
+    fn extract_writes_one_section_with_its_subsections() {
+        let config = Config::parse_from(["treetest", "testdata/dlog0.log"]);
+        let mut out = Vec::new();
+        extract(&config, "Section 2", &mut out).unwrap();
+        let text = std::fs::read_to_string("testdata/dlog0.log").unwrap();
+        let section: Vec<_> = text.lines().skip(5).take(8).collect();
+        assert_eq!(section[0], "+ Section 2");
+        assert_eq!(section[7], "");
+        assert_eq!(String::from_utf8(out).unwrap(), section.join("\n") + "\n");
+
+        let missing = extract(&config, "Section 9", &mut Vec::new());
+        assert!(
+            matches!(missing, Err(AppError::NoSection { section, .. }) if section == "Section 9")
+        );
+    }
+
+    #[test]
+    fn initial_layout() {
+        let buffer = render([]);
+        let rows = rows(&buffer);
+        assert!(rows[0].contains("Table of Contents"));
+        assert!(rows[0].contains("Log Lines"));
+        assert!(rows[1].contains("Section 1"));
+        assert!(rows[1].contains("+ Section 1"));
+        assert!(rows[2].contains("▶ Section 2"));
+        assert!(rows[3].contains("Section 3"));
+        assert!(rows[11].contains("testdata/dlog0.log"));
+        assert_eq!(selected(&buffer), "Section 1");
+    }
+
+    #[test]
+    fn expand_and_collapse() {
+        let buffer = render([key(KeyCode::Down), key(KeyCode::Right)]);
+        let nav = nav_rows(&buffer);
+        assert!(nav[2].contains("▼ Section 2"));
+        assert!(nav[3].contains("Section 2.1"));
+        assert!(nav[4].contains("Section 2.2"));
+        assert!(nav[5].contains("Section 3"));
+
+        let buffer = render([key(KeyCode::Down), key(KeyCode::Right), key(KeyCode::Left)]);
+        let nav = nav_rows(&buffer);
+        assert!(nav[2].contains("▶ Section 2"));
+        assert!(!nav.iter().any(|row| row.contains("Section 2.1")));
+        assert_eq!(selected(&buffer), "▶ Section 2");
+    }
+
+    #[test]
+    fn expand_all_collapse_all_and_open_to_depth() {
+        let nav = nav_rows(&render([key(KeyCode::Char('+'))]));
+        assert!(nav[3].contains("Section 2.1"));
+
+        let buffer = render([
+            key(KeyCode::Char('+')),
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Char('-')),
+        ]);
+        assert!(!nav_rows(&buffer)
+            .iter()
+            .any(|row| row.contains("Section 2.1")));
+        assert_eq!(selected(&buffer), "▶ Section 2");
+
+        let nav = nav_rows(&render([key(KeyCode::Char('2'))]));
+        assert!(nav[2].contains("▼ Section 2"));
+        assert!(nav[4].contains("Section 2.2"));
+        let nav = nav_rows(&render([key(KeyCode::Char('+')), key(KeyCode::Char('1'))]));
+        assert!(nav[2].contains("▶ Section 2"));
+    }
+
+    #[test]
+    fn collapse_siblings_keeps_the_selected_branch() {
+        let log = TempLog::new("collapse.log", "+ A\n++ A.1\nx\n+ B\n++ B.1\ny\n");
+        let mut app = app(&[log.path()]);
+        let tab = app.tab_mut();
+        tab.tree_key(KeyCode::Char('+'));
+        let (a, b) = (
+            tab.outline.find("A").unwrap(),
+            tab.outline.find("B").unwrap(),
+        );
+        assert!(tab.state.opened().contains(&vec![a]));
+        assert!(tab.state.opened().contains(&vec![b]));
+
+        let b1 = tab.outline.find("B/B.1").unwrap();
+        tab.state.select(tab.outline.path(b1));
+        tab.tree_key(KeyCode::Char('c'));
+        assert!(!tab.state.opened().contains(&vec![a]));
+        assert!(tab.state.opened().contains(&vec![b]));
+        assert_eq!(tab.state.selected(), tab.outline.path(b1));
+    }
+
+    #[test]
+    fn left_on_closed_root_keeps_the_selection() {
+        let buffer = render([key(KeyCode::Left)]);
+        assert_eq!(selected(&buffer), "Section 1");
+        assert_eq!(rows(&buffer), rows(&render([])));
+    }
+
+    #[test]
+    fn filter_keeps_ancestors_and_escape_restores_the_tree() {
+        let typed = "f2.2".chars().map(|c| key(KeyCode::Char(c)));
+        let buffer = render(typed.clone());
+        let nav = nav_rows(&buffer);
+        assert!(nav[1].contains("▼ Section 2"));
+        assert!(nav[2].contains("Section 2.2"));
+        assert!(!nav.iter().any(|row| row.contains("Section 1")));
+        assert!(!nav.iter().any(|row| row.contains("Section 2.1")));
+        assert!(!nav.iter().any(|row| row.contains("Section 3")));
+        // Section 2 got selected on "2", and stays selected as an ancestor.
+        assert_eq!(selected(&buffer), "▼ Section 2");
+
+        let buffer = render(typed.chain([key(KeyCode::Esc)]));
+        let nav = nav_rows(&buffer);
+        assert!(nav[2].contains("▶ Section 2"));
+        assert!(!nav.iter().any(|row| row.contains("Section 2.2")));
+        assert_eq!(selected(&buffer), "Section 1");
+    }
+
+    #[test]
+    fn page_down_scrolls_the_log() {
+        let buffer = render([key(KeyCode::Tab), key(KeyCode::PageDown)]);
+        let rows = rows(&buffer);
+        // A page is 8 lines, but the log only scrolls as far as its last full page.
+        assert!(rows[1].contains("Fusce placerat"));
+        assert!(rows[9].contains("Duis ultrices"));
+        assert!(!rows.iter().any(|row| row.contains("+ Section 1")));
+    }
+
+    #[test]
+    fn tabs_keep_their_own_state() {
+        let first = TempLog::copy("tabs-1.log");
+        let second = TempLog::new("tabs-2.log", "+ Other\nline\n");
+        let pattern = format!(
+            "{}/treetest-{}-tabs-*.log",
+            std::env::temp_dir().display(),
+            std::process::id()
+        );
+        let config = Config::parse_from(["treetest", &pattern]);
+        assert_eq!(config.paths(), [first.path(), second.path()]);
+
+        let scrolled = [key(KeyCode::Tab), key(KeyCode::PageDown)];
+        let away = scrolled.iter().cloned().chain([key(KeyCode::Char(']'))]);
+        let buffer = render_args(&[&pattern], away.clone());
+        let panes = rows(&buffer);
+        assert!(panes[2].contains("+ Other"));
+
+        let buffer = render_args(&[&pattern], away.chain([key(KeyCode::Char('['))]));
+        let panes = rows(&buffer);
+        assert!(panes[2].contains("Fusce placerat"));
+        assert!(!panes.iter().any(|row| row.contains("+ Other")));
+    }
+
+    #[test]
+    fn search_jumps_to_the_first_match_as_it_is_typed() {
+        let typed = "/placerat".chars().map(|c| key(KeyCode::Char(c)));
+        let buffer = render(typed.chain([key(KeyCode::Enter)]));
+        let rows = rows(&buffer);
+        assert!(rows[11].contains("/placerat  match 1 of 2"));
+        assert!(rows.iter().any(|row| row.contains("Fusce placerat")));
+    }
+
+    #[test]
+    fn bookmarks_are_listed_and_saved() {
+        let log = TempLog::copy("notes.log");
+
+        // Click the third log line to put the cursor there.
+        let buffer = render_file(log.path(), [click(30, 3), key(KeyCode::Char('m'))]);
+        let saved = std::fs::read_to_string(format!("{}.notes.toml", log.path())).unwrap();
+        assert!(nav_rows(&buffer)[4].contains("▶ Bookmarks [1]"));
+        assert!(rows(&buffer)[11].contains("Bookmarked line 3"));
+        assert!(saved.contains("line = 3"));
+    }
+
+    #[test]
+    fn reload_keeps_the_selection() {
+        let log = TempLog::copy("edit.log");
+        let mut app = app(&[log.path()]);
+        let tab = app.tab_mut();
+        let id = tab.outline.find("Section 2/Section 2.2").unwrap();
+        tab.state.select(tab.outline.path(id));
+
+        let text = std::fs::read_to_string("testdata/dlog0.log").unwrap();
+        log.write(format!("+ Section 0\n{text}"));
+        tab.reload().unwrap();
+        let section = tab.selected_section().unwrap();
+        assert_eq!((section.title.as_str(), section.start), ("Section 2.2", 10));
+    }
+
+    #[test]
+    fn following_picks_up_lines_written_after_loading() {
+        let log = TempLog::new("follow.log", "+ A\none\n");
+        let mut app = app(&[log.path()]);
+        log.append("two\n");
+        app.toggle_follow();
+        log.append("three\n");
+        std::thread::sleep(follow::POLL_INTERVAL);
+        assert!(app.poll_follow());
+        let lines: Vec<_> = app.tab().lines.iter().collect();
+        assert_eq!(lines, ["+ A", "one", "two", "three"]);
+    }
+
+    #[test]
+    fn stale_matches_are_not_highlighted() {
+        let found = |bytes| Match { line: 0, bytes };
+        let matches = [found(0..2), found(1..3), found(3..9), found(3..4)];
+        let line = highlight_matches("abc\u{e9}", &matches, None);
+        let text: Vec<_> = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(text, ["", "ab", "c\u{e9}"]);
+    }
+
+    #[test]
+    fn pipe_shows_the_output_over_the_log_then_in_it() {
+        let typed = "|tr a-z A-Z\r".chars().map(|c| match c {
+            '\r' => key(KeyCode::Enter),
+            c => key(KeyCode::Char(c)),
+        });
+        let overlay = rows(&render(typed.clone()));
+        assert!(overlay[1].contains("| tr a-z A-Z"));
+        assert!(overlay[2].contains("+ SECTION 1"));
+        assert!(overlay[9].contains("Enter: show in the log pane"));
+
+        let docked = rows(&render(typed.chain([key(KeyCode::Enter)])));
+        assert!(docked[0].contains("| tr a-z A-Z"));
+        assert!(docked[1].contains("+ SECTION 1"));
+        assert!(docked[10].contains("Esc: back to the log"));
+    }
+
+    #[test]
+    fn minimap_marks_sections_and_jumps_on_click() {
+        let buffer = render([]);
+        let strip: Vec<_> = rows(&buffer)
+            .iter()
+            .map(|row| row.chars().last().unwrap())
+            .collect();
+        // 16 lines on 9 rows, with sections on lines 1, 6, 7, 10 and 14.
+        assert_eq!(strip[1..10].iter().collect::<String>(), "━ ━━ ━ ━ ");
+        // The first 9 lines are in view.
+        assert_eq!(buffer.get(59, 5).bg, Color::DarkGray);
+        assert_eq!(buffer.get(59, 6).bg, Color::Reset);
+
+        let buffer = render([click(59, 9)]);
+        assert_eq!(selected(&buffer), "Section 3");
+        assert!(rows(&buffer).iter().any(|row| row.contains("+ Section 3")));
+    }
+
+    #[test]
+    fn groups_show_only_their_lines() {
+        let args = ["testdata/dlog0.log", "--group", "^(?P<key>Duis|Nullam) "];
+        let buffer = render_args(
+            &args,
+            [key(KeyCode::End), key(KeyCode::Right), key(KeyCode::Down)],
+        );
+        let nav = nav_rows(&buffer);
+        assert!(nav[4].contains("▼ Groups [2]"));
+        assert!(nav[6].contains("Nullam [2]"));
+        assert_eq!(selected(&buffer), "Duis [2]");
+        let rows = rows(&buffer);
+        assert!(rows[0].contains("Lines with key Duis"));
+        assert!(rows[1].contains("Duis pulvinar"));
+        assert!(rows[2].contains("Duis ultrices"));
+        assert!(!rows[3].contains("Lorem"));
+    }
+
+    #[test]
+    fn click_selects_a_section() {
+        let buffer = render([click(5, 3)]);
+        assert_eq!(selected(&buffer), "Section 3");
+        assert!(rows(&buffer)[1].contains("+ Section 3"));
+    }
+
+    #[test]
+    fn compare_shows_two_sections_side_by_side() {
+        let compare = [
+            key(KeyCode::Char('=')),
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Char('=')),
+        ];
+        let buffer = render(compare.clone());
+        let panes = rows(&buffer);
+        assert!(panes[0].contains("= Section 1"));
+        assert!(panes[0].contains("= Section 3"));
+        assert!(panes[1].contains("+ Section 1"));
+        assert!(panes[1].contains("+ Section 3"));
+        assert!(panes[3].contains("Duis pulvinar"));
+        assert!(panes[3].contains("Duis ultrices"));
+        assert!(panes[4].contains("Class aptent"));
+        assert_eq!(panes[11].trim(), "3 changed, 2 removed, 0 added line(s)");
+
+        let buffer = render(compare.into_iter().chain([key(KeyCode::Esc)]));
+        assert!(rows(&buffer)[0].contains("Log Lines"));
+    }
+
+    #[test]
+    fn session_restores_the_tree_scroll_and_search() {
+        let mut tab = app(&["testdata/dlog0.log"]).tabs.remove(0);
+        tab.search_for("placerat", 0);
+        tab.state.open(vec![1]);
+        tab.state.select(vec![1, 3]);
+        tab.log_offset = 4;
+        let session = tab.session().unwrap();
+        assert_eq!(session.selected, ["Section 2", "Section 2.2"]);
+
+        let mut restored = app(&["testdata/dlog0.log"]).tabs.remove(0);
+        restored.restore(session);
+        assert!(restored.state.opened().contains(&vec![1]));
+        assert_eq!(restored.state.selected(), [1, 3]);
+        assert_eq!(restored.log_offset, 4);
+        assert_eq!(restored.search.unwrap().query, "placerat");
     }
 }
@@ -1,36 +1,36 @@
 use clap::Parser;
+use logfile::terminal::{init_terminal, restore_on_panic, restore_terminal};
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use treetest::{run_app, App, Config};
+use treetest::{run_app, App, AppError, Config, TerminalEvents};
 
 fn main() -> std::io::Result<()> {
     let config = Config::parse();
 
+    if let Some(titles) = &config.extract {
+        if let Err(err) = treetest::extract(&config, titles, &mut std::io::stdout().lock()) {
+            eprintln!("treetest: {err}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    restore_on_panic();
+
     // Terminal initialization
-    crossterm::terminal::enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    crossterm::execute!(
-        stdout,
-        crossterm::terminal::EnterAlternateScreen,
-        crossterm::event::EnableMouseCapture
-    )?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    init_terminal()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
 
     // App
-    let app = App::new(&config);
-    let res = run_app(&mut terminal, app);
-
-    // restore terminal
-    crossterm::terminal::disable_raw_mode()?;
-    crossterm::execute!(
-        terminal.backend_mut(),
-        crossterm::terminal::LeaveAlternateScreen,
-        crossterm::event::DisableMouseCapture
-    )?;
+    let res = App::new(&config)
+        .and_then(|app| run_app(&mut terminal, app, &mut TerminalEvents).map_err(AppError::from));
+
+    restore_terminal()?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
-        println!("{err:?}");
+        eprintln!("treetest: {err}");
+        std::process::exit(1);
     }
 
     Ok(())
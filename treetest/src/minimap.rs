@@ -0,0 +1,136 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Style};
+use ratatui::widgets::Widget;
+use std::ops::Range;
+
+use crate::Tab;
+
+/// What a minimap row marks, from least to most important; a row shows the
+/// most important thing among its lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tick {
+    Section,
+    Match,
+    Bookmark,
+    Error,
+}
+
+impl Tick {
+    fn color(self) -> Color {
+        match self {
+            Tick::Section => Color::Blue,
+            Tick::Match => Color::Yellow,
+            Tick::Bookmark => Color::Cyan,
+            Tick::Error => Color::LightRed,
+        }
+    }
+}
+
+/// A strip mapping the whole log onto its height, with ticks for section
+/// starts, search matches, bookmarks and errors, and the rows covering the
+/// log pane highlighted.
+pub struct Minimap<'a> {
+    pub tab: &'a Tab,
+}
+
+impl Minimap<'_> {
+    /// The most important tick among `lines`.
+    fn tick(&self, lines: Range<usize>) -> Option<Tick> {
+        let tab = self.tab;
+        let any = |sorted: &[usize]| {
+            let start = sorted.partition_point(|&line| line < lines.start);
+            sorted.get(start).is_some_and(|&line| line < lines.end)
+        };
+        if any(&tab.error_lines.lines) {
+            return Some(Tick::Error);
+        }
+        let bookmarked = tab
+            .bookmarks
+            .marks
+            .partition_point(|mark| mark.line < lines.start);
+        if tab
+            .bookmarks
+            .marks
+            .get(bookmarked)
+            .is_some_and(|mark| mark.line < lines.end)
+        {
+            return Some(Tick::Bookmark);
+        }
+        if tab
+            .search
+            .as_ref()
+            .is_some_and(|search| !search.in_lines(lines.clone()).is_empty())
+        {
+            return Some(Tick::Match);
+        }
+        let sections = &tab.outline.sections;
+        let first = sections.partition_point(|section| section.start < lines.start);
+        sections
+            .get(first)
+            .is_some_and(|section| section.start < lines.end)
+            .then_some(Tick::Section)
+    }
+}
+
+impl Widget for Minimap<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows = area.height as usize;
+        let len = self.tab.lines.len();
+        if rows == 0 || len == 0 {
+            return;
+        }
+        let visible_end = (self.tab.log_offset + self.tab.log_height).min(len);
+        let viewport = row_of(self.tab.log_offset, rows, len)
+            ..=row_of(visible_end.saturating_sub(1), rows, len);
+        for row in 0..rows {
+            let mut style = Style::new();
+            if viewport.contains(&row) {
+                style = style.bg(Color::DarkGray);
+            }
+            let symbol = match self.tick(row_lines(row, rows, len)) {
+                Some(tick) => {
+                    style = style.fg(tick.color());
+                    "━"
+                }
+                None => " ",
+            };
+            buf.get_mut(area.x, area.y + row as u16)
+                .set_symbol(symbol)
+                .set_style(style);
+        }
+    }
+}
+
+/// The lines of a log with `len` lines shown on `row` of a minimap with `rows` rows.
+pub fn row_lines(row: usize, rows: usize, len: usize) -> Range<usize> {
+    let start = (row * len).div_ceil(rows);
+    let end = ((row + 1) * len).div_ceil(rows);
+    start..end
+}
+
+/// The minimap row that shows `line`.
+pub fn row_of(line: usize, rows: usize, len: usize) -> usize {
+    (line * rows / len.max(1)).min(rows.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_cover_each_line_once() {
+        for (rows, len) in [(10, 3), (10, 10), (7, 100), (3, 1000)] {
+            let mut next = 0;
+            for row in 0..rows {
+                let lines = row_lines(row, rows, len);
+                assert_eq!(lines.start, next);
+                for line in lines.clone() {
+                    assert_eq!(row_of(line, rows, len), row, "{rows} rows, {len} lines");
+                }
+                next = lines.end;
+            }
+            assert_eq!(next, len);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::Rules;
+
+/// A section of the log, introduced by a header line like `++ Section 2.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    pub depth: usize,          // nesting level given by the header, 1 for top-level
+    pub start: usize,          // index of the header line
+    pub end: usize,            // one past the last line, subsections included
+    pub parent: Option<usize>, // index of the enclosing section
+    pub children: Vec<usize>,
+}
+
+/// The section hierarchy of a log, built one line at a time.
+#[derive(Debug, Clone, Default)]
+pub struct Outline {
+    pub sections: Vec<Section>,
+    rules: Rules,
+    roots: Vec<usize>,
+    open: Vec<usize>, // sections that have not been closed by a later header
+    len: usize,       // number of lines seen so far
+}
+
+impl Outline {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    pub fn parse<S: AsRef<str>>(lines: &[S], rules: Rules) -> Self {
+        let mut outline = Self::new(rules);
+        for line in lines {
+            outline.push(line.as_ref());
+        }
+        outline
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn push(&mut self, line: &str) {
+        self.push_header(self.rules.header(line));
+    }
+
+    /// Adds a line whose header, if it is one, was already recognized with
+    /// these rules, e.g. on another thread.
+    pub fn push_header(&mut self, header: Option<(usize, &str)>) {
+        let index = self.len;
+        self.len += 1;
+
+        if let Some((depth, title)) = header {
+            // A header closes every open section at the same or a deeper level.
+            while let Some(&top) = self.open.last() {
+                if self.sections[top].depth < depth {
+                    break;
+                }
+                self.open.pop();
+            }
+
+            let id = self.sections.len();
+            let parent = self.open.last().copied();
+            match parent {
+                Some(parent) => self.sections[parent].children.push(id),
+                None => self.roots.push(id),
+            }
+            self.sections.push(Section {
+                title: title.to_owned(),
+                depth,
+                start: index,
+                end: index,
+                parent,
+                children: Vec::new(),
+            });
+            self.open.push(id);
+        }
+
+        for &open in &self.open {
+            self.sections[open].end = self.len;
+        }
+    }
+
+    /// Adds `count` lines that aren't headers.
+    pub fn push_plain(&mut self, count: usize) {
+        self.len += count;
+        for &open in &self.open {
+            self.sections[open].end = self.len;
+        }
+    }
+
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// Number of lines seen so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The innermost section containing `line`, found by binary search over
+    /// the section starts so it stays cheap on very long logs.
+    pub fn section_at(&self, line: usize) -> Option<usize> {
+        let after = self
+            .sections
+            .partition_point(|section| section.start <= line);
+        let mut current = after.checked_sub(1)?;
+        while self.sections[current].end <= line {
+            current = self.sections[current].parent?;
+        }
+        Some(current)
+    }
+
+    /// Identifier path from the root down to `section`, as used by `TreeState`.
+    pub fn path(&self, section: usize) -> Vec<usize> {
+        let mut path = vec![section];
+        let mut current = section;
+        while let Some(parent) = self.sections[current].parent {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Finds a section by its titles from the top, e.g. `Section 2/Section 2.1`.
+    /// Where titles repeat, the first section with the title is taken.
+    pub fn find(&self, titles: &str) -> Option<usize> {
+        self.find_titles(titles.split('/').map(str::trim))
+    }
+
+    /// Finds a section by its titles from the top, one per level.
+    pub fn find_titles<S: AsRef<str>>(&self, titles: impl IntoIterator<Item = S>) -> Option<usize> {
+        let mut candidates = self.roots();
+        let mut found = None;
+        for title in titles {
+            let &id = candidates
+                .iter()
+                .find(|&&id| self.sections[id].title == title.as_ref())?;
+            candidates = &self.sections[id].children;
+            found = Some(id);
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(outline: &Outline, ids: &[usize]) -> Vec<String> {
+        ids.iter()
+            .map(|&id| outline.sections[id].title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn testdata_hierarchy() {
+        let text = std::fs::read_to_string("testdata/dlog0.log").unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        let outline = Outline::parse(&lines, Rules::default());
+
+        assert_eq!(
+            titles(&outline, outline.roots()),
+            ["Section 1", "Section 2", "Section 3"]
+        );
+        let section_2 = &outline.sections[1];
+        assert_eq!(
+            titles(&outline, &section_2.children),
+            ["Section 2.1", "Section 2.2"]
+        );
+        assert_eq!((section_2.start, section_2.end), (5, 13));
+        assert_eq!(outline.sections[4].end, lines.len());
+    }
+
+    #[test]
+    fn nests_at_any_depth() {
+        let lines = ["+ a", "++ b", "+++ c", "++++ d", "x", "++ e", "+ f"];
+        let outline = Outline::parse(&lines, Rules::default());
+        assert_eq!(outline.path(3), [0, 1, 2, 3]);
+        assert_eq!(outline.sections[3].depth, 4);
+        assert_eq!((outline.sections[1].start, outline.sections[1].end), (1, 5));
+        assert_eq!(outline.sections[4].parent, Some(0));
+        assert_eq!(titles(&outline, outline.roots()), ["a", "f"]);
+    }
+
+    #[test]
+    fn skipped_levels_nest_under_the_nearest_shallower_section() {
+        let lines = ["+ a", "+++ b", "++ c"];
+        let outline = Outline::parse(&lines, Rules::default());
+        assert_eq!(outline.sections[1].parent, Some(0));
+        assert_eq!(outline.sections[2].parent, Some(0));
+    }
+
+    #[test]
+    fn duplicate_titles_are_separate_sections() {
+        let lines = ["+ same", "+ same", "++ same"];
+        let outline = Outline::parse(&lines, Rules::default());
+        assert_eq!(outline.sections.len(), 3);
+        assert_eq!(outline.roots(), [0, 1]);
+        assert_eq!(outline.sections[1].children, [2]);
+    }
+
+    #[test]
+    fn section_at_finds_the_innermost_section() {
+        let lines = ["preamble", "+ a", "++ b", "in b", "++ c", "+ d", "in d"];
+        let outline = Outline::parse(&lines, Rules::default());
+        assert_eq!(outline.section_at(0), None);
+        assert_eq!(outline.section_at(1), Some(0));
+        assert_eq!(outline.section_at(3), Some(1));
+        assert_eq!(outline.section_at(4), Some(2));
+        assert_eq!(outline.section_at(6), Some(3));
+        assert_eq!(outline.section_at(7), None);
+    }
+
+    #[test]
+    fn find_by_title_path() {
+        let lines = ["+ a", "++ b", "+ c", "++ b", "+++ d"];
+        let outline = Outline::parse(&lines, Rules::default());
+        assert_eq!(outline.find("a"), Some(0));
+        assert_eq!(outline.find("c/b"), Some(3));
+        assert_eq!(outline.find(" c / b / d "), Some(4));
+        assert_eq!(outline.find("b"), None);
+        assert_eq!(outline.find("a/d"), None);
+        assert_eq!(outline.find(""), None);
+    }
+
+    #[test]
+    fn push_extends_open_sections() {
+        let mut outline = Outline::parse(&["+ a", "++ b"], Rules::default());
+        outline.push("more");
+        assert_eq!(outline.sections[0].end, 3);
+        assert_eq!(outline.sections[1].end, 3);
+        outline.push("+ c");
+        assert_eq!(outline.sections[0].end, 3);
+        assert_eq!(outline.roots(), [0, 2]);
+        outline.push_plain(2);
+        outline.push_header(Some((2, "d")));
+        assert_eq!(outline.sections[2].end, 7);
+        assert_eq!(
+            (outline.sections[3].start, outline.sections[3].parent),
+            (6, Some(2))
+        );
+    }
+}
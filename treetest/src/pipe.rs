@@ -0,0 +1,282 @@
+use crate::store::LineStore;
+use std::io::{BufRead, BufReader, BufWriter, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+/// Lines of output kept; a command printing more is stopped.
+pub const MAX_LINES: usize = 100_000;
+
+/// What the reader threads pass on from the command.
+#[derive(Debug)]
+enum Printed {
+    Line(String),   // a line of stdout
+    Truncated,      // stdout went past `MAX_LINES` and is no longer read
+    Stderr(String), // the last non-blank line of stderr, once it's closed
+}
+
+/// What a shell command printed when given a section, shown over the log
+/// pane or, once docked, in its place. The command runs in the background
+/// and its output shows up as it arrives; dropping this kills it, along with
+/// anything it started.
+#[derive(Debug)]
+pub struct CommandOutput {
+    pub command: String,
+    pub lines: Vec<String>,
+    pub offset: usize,         // index of the first line shown
+    pub height: usize,         // lines that fit in the pane at the last draw
+    pub docked: bool,          // shown in the log pane instead of over it
+    pub running: bool,         // the command hasn't finished yet
+    pub truncated: bool,       // more than `MAX_LINES` lines were printed
+    pub error: Option<String>, // why the command failed, if it did
+    child: Child,
+    receiver: Receiver<Printed>,
+    stderr: Option<String>,
+}
+
+impl CommandOutput {
+    /// Starts `command` with `sh -c`, writing the lines of `input` to its stdin.
+    pub fn run(command: &str, input: LineStore) -> std::io::Result<Self> {
+        let mut shell = Command::new("sh");
+        shell
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // In a process group of its own, so the whole pipeline can be killed.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+        let mut child = shell.spawn()?;
+        // Read and feed the input on another thread, so neither a big section
+        // nor a command that prints before it has read everything holds up the UI.
+        let stdin = child.stdin.take().expect("stdin is piped");
+        thread::spawn(move || {
+            // The command may not read all of it, like `head`.
+            let _ = crate::tab::write_lines(&mut BufWriter::new(stdin), input.iter());
+        });
+        let (sender, receiver) = mpsc::channel();
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stdout_sender = sender.clone();
+        thread::spawn(move || read_stdout(stdout, stdout_sender));
+        thread::spawn(move || read_stderr(stderr, sender));
+        Ok(Self {
+            command: command.to_owned(),
+            lines: Vec::new(),
+            offset: 0,
+            height: 0,
+            docked: false,
+            running: true,
+            truncated: false,
+            error: None,
+            child,
+            receiver,
+            stderr: None,
+        })
+    }
+
+    /// Takes what the command printed since the last call, and notices when
+    /// it has finished. Returns whether anything changed.
+    pub fn poll(&mut self) -> bool {
+        if !self.running {
+            return false;
+        }
+        let mut update = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(printed) => {
+                    self.take(printed);
+                    update = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                // Both pipes are closed, so the command is about done.
+                Err(TryRecvError::Disconnected) => {
+                    if let Ok(Some(status)) = self.child.try_wait() {
+                        self.finish(status);
+                        update = true;
+                    }
+                    break;
+                }
+            }
+        }
+        if self.truncated && self.running {
+            self.kill();
+            update = true;
+        }
+        update
+    }
+
+    /// Blocks until the command has finished, and takes the rest of what it printed.
+    pub fn wait(&mut self) -> std::io::Result<()> {
+        while let Ok(printed) = self.receiver.recv() {
+            self.take(printed);
+            if self.truncated {
+                self.kill();
+                return Ok(());
+            }
+        }
+        let status = self.child.wait()?;
+        self.finish(status);
+        Ok(())
+    }
+
+    /// Stops the command if it's still running, along with what it started.
+    pub fn kill(&mut self) {
+        if self.running {
+            kill_group(&mut self.child);
+            let _ = self.child.wait();
+            self.running = false;
+        }
+    }
+
+    fn take(&mut self, printed: Printed) {
+        match printed {
+            Printed::Line(line) => self.lines.push(line),
+            Printed::Truncated => self.truncated = true,
+            Printed::Stderr(line) => self.stderr = Some(line),
+        }
+    }
+
+    fn finish(&mut self, status: std::process::ExitStatus) {
+        self.running = false;
+        let command = &self.command;
+        if !status.success() {
+            self.error = Some(match &self.stderr {
+                Some(line) => format!("{command}: {line} ({status})"),
+                None => format!("{command}: {status}"),
+            });
+        }
+    }
+
+    /// Scrolls down by `lines`, keeping the last page in view.
+    pub fn scroll_down(&mut self, lines: usize) -> bool {
+        let last_page = self.lines.len().saturating_sub(self.height);
+        let offset = self
+            .offset
+            .saturating_add(lines)
+            .min(last_page.max(self.offset));
+        std::mem::replace(&mut self.offset, offset) != offset
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) -> bool {
+        let offset = self.offset.saturating_sub(lines);
+        std::mem::replace(&mut self.offset, offset) != offset
+    }
+}
+
+impl Drop for CommandOutput {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Kills the process group `child` leads.
+#[cfg(unix)]
+fn kill_group(child: &mut Child) {
+    // SAFETY: killpg only sends a signal; the group is still there, as the
+    // child hasn't been waited for.
+    unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+}
+
+#[cfg(not(unix))]
+fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Passes on the lines of `stdout`, up to `MAX_LINES`.
+fn read_stdout(stdout: impl Read, sender: Sender<Printed>) {
+    for (count, line) in BufReader::new(stdout).split(b'\n').enumerate() {
+        let Ok(line) = line else {
+            return;
+        };
+        if count == MAX_LINES {
+            let _ = sender.send(Printed::Truncated);
+            return;
+        }
+        let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line)).into_owned();
+        // Also stop once the output has been closed.
+        if sender.send(Printed::Line(line)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Passes on the last non-blank line of `stderr`, to explain a failure.
+fn read_stderr(stderr: impl Read, sender: Sender<Printed>) {
+    let mut last = None;
+    for line in BufReader::new(stderr).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    if let Some(line) = last {
+        let _ = sender.send(Printed::Stderr(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(lines: &[&str]) -> LineStore {
+        LineStore::new(lines.iter().map(|&line| line.to_owned()).collect())
+    }
+
+    #[test]
+    fn pipes_lines_through_the_shell() {
+        let mut output = CommandOutput::run("sort | uniq -c", input(&["b", "a", "b"])).unwrap();
+        output.wait().unwrap();
+        let lines: Vec<_> = output.lines.iter().map(|line| line.trim()).collect();
+        assert_eq!(lines, ["1 a", "2 b"]);
+        assert_eq!(output.error, None);
+
+        let mut output =
+            CommandOutput::run("echo partial; echo oops >&2; exit 3", input(&["x"])).unwrap();
+        output.wait().unwrap();
+        assert_eq!(output.lines, ["partial"]);
+        assert!(output
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("oops")));
+    }
+
+    #[test]
+    fn endless_output_is_cut_off() {
+        let mut output = CommandOutput::run("yes", input(&[])).unwrap();
+        output.wait().unwrap();
+        assert!(output.truncated);
+        assert!(!output.running);
+        assert_eq!(output.lines.len(), MAX_LINES);
+    }
+
+    #[test]
+    fn kill_stops_a_running_command_and_its_children() {
+        let mut output = CommandOutput::run("sleep 30 & echo $!; wait", input(&[])).unwrap();
+        while output.lines.is_empty() {
+            output.poll();
+        }
+        output.kill();
+        assert!(!output.running);
+        // The backgrounded sleep went with the shell.
+        let sleep = &output.lines[0];
+        let alive = || {
+            Command::new("kill")
+                .args(["-0", sleep])
+                .status()
+                .unwrap()
+                .success()
+        };
+        let start = std::time::Instant::now();
+        while alive() {
+            assert!(
+                start.elapsed().as_secs() < 5,
+                "sleep {sleep} is still running"
+            );
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Built-in section header conventions.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// `+ Section`, `++ Subsection`, ...
+    Plus,
+    /// Markdown headings: `# Title`, `## Subtitle`, ...
+    Markdown,
+    /// Banners like `=== STEP 3 ===`
+    Banner,
+}
+
+impl Preset {
+    pub fn rule(self) -> Rule {
+        let (pattern, depth) = match self {
+            Preset::Plus => (r"^(?P<depth>\++)\s+(?P<title>\S.*?)\s*$", Depth::Length),
+            Preset::Markdown => (
+                r"^(?P<depth>#{1,6})\s+(?P<title>\S.*?)(\s+#+)?\s*$",
+                Depth::Length,
+            ),
+            Preset::Banner => (r"^={3,}\s*(?P<title>\S.*?)\s*={3,}\s*$", Depth::Fixed(1)),
+        };
+        Rule::new(pattern, depth).expect("preset patterns are valid")
+    }
+}
+
+/// How a rule derives the nesting depth of the headers it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// Every header is at this depth.
+    Fixed(usize),
+    /// The number of characters in the `depth` capture, so `###` is 3.
+    Length,
+    /// The `depth` capture is the depth as a number.
+    Value,
+}
+
+/// A regex recognizing section headers. The `title` capture (or the whole
+/// match, without one) becomes the section title.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    regex: Regex,
+    depth: Depth,
+}
+
+impl Rule {
+    pub fn new(pattern: &str, depth: Depth) -> std::io::Result<Self> {
+        let regex = Regex::new(pattern).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+        if matches!(depth, Depth::Length | Depth::Value) && !has_capture(&regex, "depth") {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("rule `{pattern}` needs a `depth` capture"),
+            ));
+        }
+        Ok(Self { regex, depth })
+    }
+
+    /// A rule from a command line pattern: depth comes from the length of
+    /// its `depth` capture if it has one, otherwise every header is top-level.
+    pub fn from_pattern(pattern: &str) -> std::io::Result<Self> {
+        let regex = Regex::new(pattern).map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+        let depth = if has_capture(&regex, "depth") {
+            Depth::Length
+        } else {
+            Depth::Fixed(1)
+        };
+        Ok(Self { regex, depth })
+    }
+
+    /// Returns the depth and title if `line` is a header.
+    pub fn header<'a>(&self, line: &'a str) -> Option<(usize, &'a str)> {
+        let captures = self.regex.captures(line)?;
+        let title = captures
+            .name("title")
+            .or_else(|| captures.get(0))?
+            .as_str()
+            .trim();
+        if title.is_empty() {
+            return None;
+        }
+        let depth = match self.depth {
+            Depth::Fixed(depth) => depth,
+            Depth::Length => captures.name("depth")?.as_str().chars().count(),
+            Depth::Value => captures.name("depth")?.as_str().parse().ok()?,
+        };
+        Some((depth.max(1), title))
+    }
+}
+
+fn has_capture(regex: &Regex, name: &str) -> bool {
+    regex
+        .capture_names()
+        .flatten()
+        .any(|capture| capture == name)
+}
+
+/// The rules used to find section headers; the first one that matches a line wins.
+#[derive(Debug, Clone)]
+pub struct Rules(Vec<Rule>);
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self(vec![Preset::Plus.rule()])
+    }
+}
+
+/// The contents of a rules file, e.g.
+///
+/// ```toml
+/// presets = ["markdown"]
+///
+/// [[rules]]
+/// pattern = '\[phase:(?P<title>[^\]]+)\]'
+/// depth = 2
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+    #[serde(default)]
+    presets: Vec<Preset>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    pattern: String,
+    #[serde(default)]
+    depth: Option<DepthEntry>,
+}
+
+/// `depth = 2`, `depth = "length"` or `depth = "value"`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum DepthEntry {
+    Fixed(usize),
+    Derived(DerivedDepth),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum DerivedDepth {
+    Length,
+    Value,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self(rules)
+    }
+
+    pub fn from_presets(presets: &[Preset]) -> Self {
+        Self(presets.iter().map(|preset| preset.rule()).collect())
+    }
+
+    /// Parses a TOML rules file. Its custom rules are tried before its presets.
+    pub fn from_toml(text: &str) -> std::io::Result<Self> {
+        let file: RulesFile =
+            toml::from_str(text).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let mut rules = Vec::new();
+        for entry in file.rules {
+            rules.push(match entry.depth {
+                None => Rule::from_pattern(&entry.pattern)?,
+                Some(DepthEntry::Fixed(depth)) => Rule::new(&entry.pattern, Depth::Fixed(depth))?,
+                Some(DepthEntry::Derived(DerivedDepth::Length)) => {
+                    Rule::new(&entry.pattern, Depth::Length)?
+                }
+                Some(DepthEntry::Derived(DerivedDepth::Value)) => {
+                    Rule::new(&entry.pattern, Depth::Value)?
+                }
+            });
+        }
+        rules.extend(file.presets.iter().map(|preset| preset.rule()));
+        Ok(Self(rules))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Appends `other`'s rules after these, so these take precedence.
+    pub fn extend(&mut self, other: Rules) {
+        self.0.extend(other.0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the depth and title if `line` is a section header.
+    pub fn header<'a>(&self, line: &'a str) -> Option<(usize, &'a str)> {
+        self.0.iter().find_map(|rule| rule.header(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plus_preset() {
+        let rules = Rules::default();
+        assert_eq!(rules.header("+ Section 1"), Some((1, "Section 1")));
+        assert_eq!(rules.header("++ Section 2.1 "), Some((2, "Section 2.1")));
+        assert_eq!(rules.header("++++ deep"), Some((4, "deep")));
+        assert_eq!(rules.header("+Section"), None);
+        assert_eq!(rules.header("++ "), None);
+        assert_eq!(rules.header("Lorem ipsum"), None);
+    }
+
+    #[test]
+    fn markdown_preset() {
+        let rules = Rules::from_presets(&[Preset::Markdown]);
+        assert_eq!(rules.header("# Title"), Some((1, "Title")));
+        assert_eq!(rules.header("### Closed ###"), Some((3, "Closed")));
+        assert_eq!(rules.header("#hashtag"), None);
+        assert_eq!(rules.header("####### too deep"), None);
+    }
+
+    #[test]
+    fn banner_preset() {
+        let rules = Rules::from_presets(&[Preset::Banner]);
+        assert_eq!(rules.header("=== STEP 3 ==="), Some((1, "STEP 3")));
+        assert_eq!(rules.header("=====build====="), Some((1, "build")));
+        assert_eq!(rules.header("== short =="), None);
+        assert_eq!(rules.header("======"), None);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut rules = Rules::new(vec![
+            Rule::new(r"^\+ (?P<title>special)$", Depth::Fixed(3)).unwrap()
+        ]);
+        rules.extend(Rules::default());
+        assert_eq!(rules.header("+ special"), Some((3, "special")));
+        assert_eq!(rules.header("+ other"), Some((1, "other")));
+    }
+
+    #[test]
+    fn pattern_without_title_uses_whole_match() {
+        let rule = Rule::from_pattern(r"\[phase:\w+\]").unwrap();
+        assert_eq!(
+            rule.header("12:00:01 [phase:build] starting"),
+            Some((1, "[phase:build]"))
+        );
+    }
+
+    #[test]
+    fn pattern_with_depth_capture_uses_its_length() {
+        let rule = Rule::from_pattern(r"^(?P<depth>-+)> (?P<title>.*)").unwrap();
+        assert_eq!(rule.header("---> third"), Some((3, "third")));
+    }
+
+    #[test]
+    fn derived_depth_needs_a_capture() {
+        assert!(Rule::new(r"^(?P<title>.*)$", Depth::Length).is_err());
+        assert!(Rule::new(r"^(?P<title>.*)$", Depth::Fixed(2)).is_ok());
+        assert!(Rule::from_pattern("(unclosed").is_err());
+    }
+
+    #[test]
+    fn rules_file() {
+        let rules = Rules::from_toml(
+            r#"
+            presets = ["banner"]
+
+            [[rules]]
+            pattern = '\[phase:(?P<title>[^\]]+)\]'
+            depth = 2
+
+            [[rules]]
+            pattern = '^STEP (?P<depth>\d+): (?P<title>.*)'
+            depth = "value"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(rules.header("10:00 [phase:test] go"), Some((2, "test")));
+        assert_eq!(rules.header("STEP 4: link"), Some((4, "link")));
+        assert_eq!(rules.header("=== done ==="), Some((1, "done")));
+        assert_eq!(rules.header("+ not a rule here"), None);
+    }
+
+    #[test]
+    fn bad_rules_file() {
+        assert!(Rules::from_toml("presets = [\"nope\"]").is_err());
+        assert!(Rules::from_toml("[[rules]]\npattern = '('").is_err());
+        assert!(Rules::from_toml("[[rules]]\npattern = 'x'\ndepth = \"wide\"").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use regex::Regex;
+use std::ops::Range;
+
+/// Matches kept at most; a search stops once it has found this many.
+pub const MAX_MATCHES: usize = 100_000;
+
+/// One occurrence of the search pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    pub bytes: Range<usize>, // byte range within the line
+}
+
+/// A search over the log lines. The query is used as a regex when it
+/// compiles, and as a literal otherwise. Lines are searched a part at a
+/// time, from the first, so a big log doesn't have to be searched at once.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub query: String,
+    regex: Option<Regex>,
+    pub matches: Vec<Match>,    // sorted by line, then by position
+    pub current: Option<usize>, // index into `matches`
+    pub searched: usize,        // lines searched so far
+}
+
+impl Search {
+    /// A search for `query` that has yet to look at any lines.
+    pub fn new(query: &str) -> Self {
+        let regex = if query.is_empty() {
+            None
+        } else {
+            Regex::new(query)
+                .or_else(|_| Regex::new(&regex::escape(query)))
+                .ok()
+        };
+        Self {
+            query: query.to_owned(),
+            regex,
+            matches: Vec::new(),
+            current: None,
+            searched: 0,
+        }
+    }
+
+    /// Searches `lines`, the lines after those searched so far, until
+    /// `MAX_MATCHES` have been found.
+    pub fn extend<S: AsRef<str>>(&mut self, lines: impl IntoIterator<Item = S>) {
+        let Some(regex) = &self.regex else {
+            return;
+        };
+        for line in lines {
+            if self.is_full() {
+                break;
+            }
+            let index = self.searched;
+            self.matches.extend(
+                regex
+                    .find_iter(line.as_ref())
+                    .filter(|m| !m.is_empty())
+                    .map(|m| Match {
+                        line: index,
+                        bytes: m.range(),
+                    }),
+            );
+            self.searched += 1;
+        }
+        self.matches.truncate(MAX_MATCHES);
+    }
+
+    /// Whether the search has stopped for having found `MAX_MATCHES`.
+    pub fn is_full(&self) -> bool {
+        self.matches.len() >= MAX_MATCHES
+    }
+
+    /// Whether there's nothing left to search among `lines` lines.
+    pub fn is_done(&self, lines: usize) -> bool {
+        self.regex.is_none() || self.is_full() || self.searched >= lines
+    }
+
+    /// The matches on `lines`, as a slice of `matches`.
+    pub fn in_lines(&self, lines: Range<usize>) -> &[Match] {
+        let start = self.matches.partition_point(|m| m.line < lines.start);
+        let end = self.matches.partition_point(|m| m.line < lines.end);
+        &self.matches[start..end]
+    }
+
+    pub fn current_match(&self) -> Option<&Match> {
+        self.matches.get(self.current?)
+    }
+
+    /// Makes the first match at or after `line` current, wrapping around.
+    pub fn seek(&mut self, line: usize) -> Option<&Match> {
+        if self.matches.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let index = self.matches.partition_point(|m| m.line < line);
+        self.current = Some(index % self.matches.len());
+        self.current_match()
+    }
+
+    pub fn next_match(&mut self) -> Option<&Match> {
+        let len = self.matches.len();
+        if len == 0 {
+            return None;
+        }
+        self.current = Some(self.current.map_or(0, |current| (current + 1) % len));
+        self.current_match()
+    }
+
+    pub fn previous_match(&mut self) -> Option<&Match> {
+        let len = self.matches.len();
+        if len == 0 {
+            return None;
+        }
+        self.current = Some(
+            self.current
+                .map_or(len - 1, |current| (current + len - 1) % len),
+        );
+        self.current_match()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_in_parts_until_full() {
+        let mut search = Search::new("b+");
+        search.extend(["abba", "c"]);
+        assert!(!search.is_done(3));
+        search.extend(["bb b"]);
+        assert!(search.is_done(3));
+        assert_eq!(
+            search.matches,
+            [
+                Match {
+                    line: 0,
+                    bytes: 1..3
+                },
+                Match {
+                    line: 2,
+                    bytes: 0..2
+                },
+                Match {
+                    line: 2,
+                    bytes: 3..4
+                },
+            ]
+        );
+
+        let mut search = Search::new("x");
+        search.extend(std::iter::repeat_n("x x", MAX_MATCHES));
+        assert!(search.is_full());
+        assert_eq!(search.matches.len(), MAX_MATCHES);
+        assert_eq!(search.searched, MAX_MATCHES / 2);
+    }
+}
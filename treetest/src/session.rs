@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use crate::bookmarks::line_hash;
+use crate::state_dir;
+
+/// Where a log file was left: the tree, the scroll position and the search.
+/// It's restored when the same file is opened again, unless the file has
+/// changed since. Tree nodes are kept by their section titles, so they're
+/// found again even if the header rules changed in between.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Session {
+    pub path: String,             // canonical path of the log file
+    pub size: u64,                // size of the file when the session was saved
+    pub mtime: u64,               // its modification time then, in seconds since the epoch
+    pub opened: Vec<Vec<String>>, // title paths of the opened sections
+    pub selected: Vec<String>,    // title path of the selected section
+    pub log_offset: usize,
+    pub search: Option<String>, // query of the last search
+}
+
+impl Session {
+    /// An empty session for the log file at `path` as it is now.
+    pub fn for_file(path: &str) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let mtime = metadata.modified()?.duration_since(UNIX_EPOCH);
+        Ok(Self {
+            path: std::fs::canonicalize(path)?.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            mtime: mtime.map_or(0, |mtime| mtime.as_secs()),
+            ..Self::default()
+        })
+    }
+
+    pub fn from_toml(text: &str) -> std::io::Result<Self> {
+        toml::from_str(text).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("session serializes")
+    }
+
+    /// Whether this session was saved for the same file as `other`, unchanged.
+    pub fn same_file(&self, other: &Session) -> bool {
+        (&self.path, self.size, self.mtime) == (&other.path, other.size, other.mtime)
+    }
+
+    /// The saved session of the log file at `path`, if there is one for the
+    /// file as it is now.
+    pub fn load(path: &str) -> Option<Self> {
+        let current = Self::for_file(path).ok()?;
+        let text = std::fs::read_to_string(Self::file(&current.path)?).ok()?;
+        Self::from_toml(&text)
+            .ok()
+            .filter(|saved| saved.same_file(&current))
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let file = Self::file(&self.path)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no home directory"))?;
+        std::fs::create_dir_all(file.parent().expect("session files are in a directory"))?;
+        std::fs::write(file, self.to_toml())
+    }
+
+    /// Where the session of the log file with the canonical path `path` is
+    /// kept: one file per log in `state_dir()/sessions`, named by a hash of
+    /// the path, so a changed file's session gets replaced.
+    fn file(path: &str) -> Option<PathBuf> {
+        let name = format!("{:016x}.toml", line_hash(path));
+        Some(state_dir()?.join("sessions").join(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_for_the_same_file() {
+        let mut session = Session::for_file("testdata/dlog0.log").unwrap();
+        assert!(session.path.ends_with("testdata/dlog0.log"));
+        let path = |titles: &[&str]| titles.iter().map(|&title| title.to_owned()).collect();
+        session.opened = vec![path(&["Section 2"]), path(&["Section 2", "Section 2.2"])];
+        session.selected = path(&["Section 2", "Section 2.2"]);
+        session.log_offset = 9;
+        session.search = Some("placerat".to_owned());
+        let saved = Session::from_toml(&session.to_toml()).unwrap();
+        assert_eq!(saved, session);
+
+        let current = Session::for_file("testdata/dlog0.log").unwrap();
+        assert!(saved.same_file(&current));
+        let grown = Session {
+            size: current.size + 1,
+            ..current
+        };
+        assert!(!saved.same_file(&grown));
+    }
+}
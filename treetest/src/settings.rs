@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+/// Layout choices that carry over between runs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Settings {
+    pub nav_width: u16,   // columns of the table of contents pane, borders included
+    pub nav_hidden: bool, // the table of contents pane is collapsed
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            nav_width: 20,
+            nav_hidden: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_toml(text: &str) -> std::io::Result<Self> {
+        toml::from_str(text).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("settings serialize")
+    }
+
+    /// The saved settings, or the defaults if there are none or they can't be read.
+    pub fn load() -> Self {
+        state_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join("settings.toml")).ok())
+            .and_then(|text| Self::from_toml(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let dir =
+            state_dir().ok_or_else(|| Error::new(ErrorKind::NotFound, "no home directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("settings.toml"), self.to_toml())
+    }
+}
+
+/// Where treetest keeps its state: `$XDG_STATE_HOME/treetest`, by default
+/// `~/.local/state/treetest`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("treetest"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let settings = Settings {
+            nav_width: 42,
+            nav_hidden: true,
+        };
+        assert_eq!(Settings::from_toml(&settings.to_toml()).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings = Settings::from_toml("nav_width = 30").unwrap();
+        assert_eq!((settings.nav_width, settings.nav_hidden), (30, false));
+        assert!(Settings::from_toml("nav_width = \"wide\"").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::Outline;
+use ratatui::prelude::{Color, Span, Style};
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Which statistic the tree labels show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Stat {
+    #[default]
+    None,
+    Lines,
+    Problems,
+    Duration,
+}
+
+impl Stat {
+    /// The stat shown after this one when cycling.
+    pub fn next(self) -> Self {
+        match self {
+            Stat::None => Stat::Lines,
+            Stat::Lines => Stat::Problems,
+            Stat::Problems => Stat::Duration,
+            Stat::Duration => Stat::None,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Stat::None => "no stats",
+            Stat::Lines => "line counts",
+            Stat::Problems => "error and warning counts",
+            Stat::Duration => "elapsed time",
+        }
+    }
+}
+
+/// Statistics for the lines of a section, subsections included.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub lines: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    first: Option<(usize, f64)>, // line index and seconds of the first timestamp
+    last: Option<(usize, f64)>,  // and of the last
+}
+
+impl Stats {
+    /// Time between the first and last timestamp, if there are two.
+    pub fn duration(&self) -> Option<Duration> {
+        let ((first_line, first), (last_line, last)) = (self.first?, self.last?);
+        if first_line == last_line {
+            return None;
+        }
+        // Without dates, a negative span means the log went past midnight.
+        let seconds = if last < first {
+            last + DAY - first
+        } else {
+            last - first
+        };
+        Some(Duration::from_secs_f64(seconds.max(0.0)))
+    }
+
+    fn add(&mut self, other: &Stats) {
+        self.lines += other.lines;
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        self.first = match (self.first, other.first) {
+            (Some(a), Some(b)) => Some(if b.0 < a.0 { b } else { a }),
+            (a, b) => a.or(b),
+        };
+        self.last = match (self.last, other.last) {
+            (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn add_line(&mut self, index: usize, line: &str, error: &Regex) {
+        self.lines += 1;
+        if error.is_match(line) {
+            self.errors += 1;
+        } else if warning().is_match(line) {
+            self.warnings += 1;
+        }
+        if let Some(seconds) = timestamp(line) {
+            self.first.get_or_insert((index, seconds));
+            self.last = Some((index, seconds));
+        }
+    }
+
+    /// The spans appended to a tree label to show `stat`.
+    pub fn badge(&self, stat: Stat) -> Vec<Span<'static>> {
+        let dim = Style::new().fg(Color::Gray);
+        match stat {
+            Stat::None => Vec::new(),
+            Stat::Lines => vec![Span::styled(format!(" {}L", self.lines), dim)],
+            Stat::Problems => {
+                let mut spans = Vec::new();
+                if self.errors > 0 {
+                    spans.push(Span::styled(
+                        format!(" {}E", self.errors),
+                        Style::new().fg(Color::LightRed),
+                    ));
+                }
+                if self.warnings > 0 {
+                    spans.push(Span::styled(
+                        format!(" {}W", self.warnings),
+                        Style::new().fg(Color::Yellow),
+                    ));
+                }
+                spans
+            }
+            Stat::Duration => self
+                .duration()
+                .map(|duration| vec![Span::styled(format!(" {}", format_duration(duration)), dim)])
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Counts `lines`, the first of which is line `first` of the log, towards
+/// the stats of their innermost sections in `own`, one entry per section of
+/// `outline`. Lines that match `error` count as errors. A line's innermost
+/// section doesn't change as lines are appended, so a growing log can be
+/// counted a part at a time.
+pub fn count_lines<S: AsRef<str>>(
+    own: &mut [Stats],
+    outline: &Outline,
+    lines: impl IntoIterator<Item = S>,
+    first: usize,
+    error: &Regex,
+) {
+    let Some(&root) = outline.roots().first() else {
+        return;
+    };
+    let skip = outline.sections[root].start.saturating_sub(first);
+    for (index, line) in (first..).zip(lines).skip(skip) {
+        if let Some(section) = outline.section_at(index) {
+            own[section].add_line(index, line.as_ref(), error);
+        }
+    }
+}
+
+/// The stats of each section with its subsections', from `own`, each
+/// section's stats without them.
+pub fn roll_up(outline: &Outline, own: &[Stats]) -> Vec<Stats> {
+    let mut stats = own.to_vec();
+    // Subsections always come after their parent.
+    for id in (0..stats.len()).rev() {
+        if let Some(parent) = outline.sections[id].parent {
+            let child = stats[id];
+            stats[parent].add(&child);
+        }
+    }
+    stats
+}
+
+fn warning() -> &'static Regex {
+    static WARNING: OnceLock<Regex> = OnceLock::new();
+    WARNING.get_or_init(|| Regex::new(r"\bWARN(ING)?\b").unwrap())
+}
+
+/// Seconds since the epoch, or since midnight when the timestamp has no date,
+/// of the first `[YYYY-MM-DD[T ]]HH:MM:SS[.fff]` timestamp in `line`.
+fn timestamp(line: &str) -> Option<f64> {
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+    let regex = TIMESTAMP.get_or_init(|| {
+        // `\d` would also match other scripts' digits, which don't parse as numbers.
+        Regex::new(
+            r"(?:\b([0-9]{4})-([0-9]{2})-([0-9]{2})[T ]|\b)([0-9]{2}):([0-9]{2}):([0-9]{2})(?:[.,]([0-9]{1,9}))?",
+        )
+        .unwrap()
+    });
+    let captures = regex.captures(line)?;
+    let number = |index| captures.get(index)?.as_str().parse::<i64>().ok();
+    let (hours, minutes, seconds) = (number(4)?, number(5)?, number(6)?);
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days = match (number(1), number(2), number(3)) {
+        (Some(year), Some(month), Some(day)) => days_from_civil(year, month, day),
+        _ => 0,
+    };
+    let fraction = match captures.get(7) {
+        Some(m) => format!("0.{}", m.as_str()).parse().ok()?,
+        None => 0.0,
+    };
+    Some((days * 86_400 + hours * 3600 + minutes * 60 + seconds) as f64 + fraction)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if seconds < 3600 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{:02}m", seconds / 3600, seconds / 60 % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rules;
+
+    fn section_stats(outline: &Outline, lines: &[&str], error: &Regex) -> Vec<Stats> {
+        let mut own = vec![Stats::default(); outline.sections.len()];
+        count_lines(&mut own, outline, lines, 0, error);
+        roll_up(outline, &own)
+    }
+
+    #[test]
+    fn counts_roll_up_to_parents() {
+        let lines = [
+            "+ build",
+            "ERROR one",
+            "++ link",
+            "WARN two",
+            "WARNING three",
+            "++ test",
+            "ERROR: four",
+            "ERRORS are not errors",
+        ];
+        let outline = Outline::parse(&lines, Rules::default());
+        let stats = section_stats(&outline, &lines, &Regex::new(r"\bERROR\b").unwrap());
+        assert_eq!(
+            (stats[0].lines, stats[0].errors, stats[0].warnings),
+            (8, 2, 2)
+        );
+        assert_eq!(
+            (stats[1].lines, stats[1].errors, stats[1].warnings),
+            (3, 0, 2)
+        );
+        assert_eq!(
+            (stats[2].lines, stats[2].errors, stats[2].warnings),
+            (3, 1, 0)
+        );
+    }
+
+    #[test]
+    fn counting_in_parts_adds_up() {
+        let lines = [
+            "+ a",
+            "ERROR one",
+            "++ b",
+            "WARN two",
+            "ERROR three",
+            "+ c",
+            "x",
+        ];
+        let outline = Outline::parse(&lines, Rules::default());
+        let error = Regex::new("ERROR").unwrap();
+        let mut own = vec![Stats::default(); outline.sections.len()];
+        count_lines(&mut own, &outline, &lines[..4], 0, &error);
+        count_lines(&mut own, &outline, &lines[4..], 4, &error);
+        assert_eq!(
+            roll_up(&outline, &own),
+            section_stats(&outline, &lines, &error)
+        );
+    }
+
+    #[test]
+    fn duration_spans_first_to_last_timestamp() {
+        let lines = [
+            "+ run",
+            "10:00:00 start",
+            "++ step",
+            "10:00:30.5 step",
+            "10:02:00 more",
+            "+ night",
+            "23:59:50 late",
+            "00:00:10 early",
+            "+ single",
+            "12:00:00 alone",
+        ];
+        let outline = Outline::parse(&lines, Rules::default());
+        let stats = section_stats(&outline, &lines, &Regex::new("ERROR").unwrap());
+        assert_eq!(stats[0].duration(), Some(Duration::from_secs(120)));
+        assert_eq!(stats[1].duration(), Some(Duration::from_secs_f64(89.5)));
+        assert_eq!(stats[2].duration(), Some(Duration::from_secs(20)));
+        assert_eq!(stats[3].duration(), None);
+    }
+
+    #[test]
+    fn dated_timestamps() {
+        assert_eq!(timestamp("1970-01-02T00:00:01Z"), Some(86_401.0));
+        assert_eq!(
+            timestamp("[2024-03-01 12:00:00,250] x"),
+            Some(1_709_294_400.25)
+        );
+        assert_eq!(timestamp("no time here"), None);
+        assert_eq!(timestamp("99:99:99"), None);
+        assert_eq!(timestamp("１２:３０:４５ fullwidth"), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(2500)), "2.5s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m05s");
+        assert_eq!(format_duration(Duration::from_secs(7380)), "2h03m");
+    }
+}
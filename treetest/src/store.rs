@@ -0,0 +1,284 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::ops::Range;
+
+/// Indexed lines read at a time when iterating.
+const CHUNK_LINES: usize = 4096;
+
+/// Indexed lines per checkpoint: only every `CHECKPOINT_LINES`th line's
+/// offset is kept, and the lines in between are found by reading forward.
+pub const CHECKPOINT_LINES: usize = 64;
+
+/// The text of a log. Plain files are only indexed, and their lines read
+/// from the file as they're needed; lines read any other way (followed
+/// appends, stdin, compressed files) are kept in memory after the indexed ones.
+///
+/// Indexed lines are read with positioned reads rather than through a memory
+/// map, so a file truncated underneath the viewer yields short or empty lines
+/// until follow mode notices, instead of a SIGBUS.
+#[derive(Debug, Default)]
+pub struct LineStore {
+    file: Option<File>,
+    checkpoints: Vec<u64>, // byte offset in `file` of every `CHECKPOINT_LINES`th indexed line
+    skipped: usize, // lines of the first checkpoint's block before the first indexed line, in a slice
+    indexed: usize, // number of indexed lines
+    indexed_end: u64, // byte offset just past the last indexed line, or its block in a slice
+    owned: Vec<String>,
+}
+
+impl LineStore {
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            owned: lines,
+            ..Self::default()
+        }
+    }
+
+    /// A store for `file`, whose lines are added by `push_indexed` as they are indexed.
+    pub fn indexed(file: File) -> Self {
+        Self {
+            file: Some(file),
+            ..Self::default()
+        }
+    }
+
+    /// Adds `lines` indexed lines, with `checkpoints` the offsets of those
+    /// that are a multiple of `CHECKPOINT_LINES` into the file, the last line
+    /// ending at `end`.
+    pub fn push_indexed(&mut self, checkpoints: &[u64], lines: usize, end: u64) {
+        debug_assert!(self.owned.is_empty(), "indexed lines come first");
+        self.checkpoints.extend_from_slice(checkpoints);
+        self.indexed += lines;
+        self.indexed_end = end;
+        debug_assert_eq!(
+            self.checkpoints.len(),
+            self.indexed.div_ceil(CHECKPOINT_LINES)
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.indexed + self.owned.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The text of line `index`, without its line terminator. Invalid UTF-8
+    /// in indexed lines is replaced.
+    pub fn get(&self, index: usize) -> Cow<'_, str> {
+        if index >= self.indexed {
+            return Cow::Borrowed(&self.owned[index - self.indexed]);
+        }
+        let line = self.read_lines(index..index + 1).pop();
+        Cow::Owned(line.expect("one line is read"))
+    }
+
+    /// The lines from `first` on.
+    pub fn iter_from(&self, first: usize) -> impl Iterator<Item = Cow<'_, str>> {
+        let indexed = self.indexed;
+        // Indexed lines are read a chunk at a time rather than one by one.
+        let chunks = (first.min(indexed)..indexed)
+            .step_by(CHUNK_LINES)
+            .flat_map(move |chunk| self.read_lines(chunk..(chunk + CHUNK_LINES).min(indexed)))
+            .map(Cow::Owned);
+        let owned = self.owned[first.saturating_sub(indexed).min(self.owned.len())..].iter();
+        chunks.chain(owned.map(|line| Cow::Borrowed(line.as_str())))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        self.iter_from(0)
+    }
+
+    /// A store of just the lines in `range`, sharing this one's file, so they
+    /// can be read on another thread.
+    pub fn slice(&self, range: Range<usize>) -> std::io::Result<Self> {
+        let indexed = range.start.min(self.indexed)..range.end.min(self.indexed);
+        let owned =
+            range.start.saturating_sub(self.indexed)..range.end.saturating_sub(self.indexed);
+        let mut slice = Self {
+            owned: self.owned[owned].to_vec(),
+            ..Self::default()
+        };
+        if let (Some(file), false) = (&self.file, indexed.is_empty()) {
+            let first = self.skipped + indexed.start;
+            let first_block = first / CHECKPOINT_LINES;
+            let last_block = (self.skipped + indexed.end - 1) / CHECKPOINT_LINES;
+            slice.file = Some(file.try_clone()?);
+            slice.indexed_end = self.block_range(last_block).end;
+            slice.checkpoints = self.checkpoints[first_block..=last_block].to_vec();
+            slice.skipped = first % CHECKPOINT_LINES;
+            slice.indexed = indexed.len();
+        }
+        Ok(slice)
+    }
+
+    pub fn extend(&mut self, lines: Vec<String>) {
+        self.owned.extend(lines);
+    }
+
+    /// Appends `text` to the last line, which was missing its line terminator.
+    pub fn continue_last(&mut self, text: &str) {
+        if self.owned.is_empty() {
+            if self.indexed == 0 {
+                return;
+            }
+            let last = self.skipped + self.indexed - 1;
+            let block = last / CHECKPOINT_LINES;
+            let block_start = self.checkpoints[block];
+            let mut bytes = self.read(block_start..self.indexed_end);
+            let start: usize = split_lines(&bytes)
+                .take(last % CHECKPOINT_LINES)
+                .map(<[u8]>::len)
+                .sum();
+            if last.is_multiple_of(CHECKPOINT_LINES) {
+                self.checkpoints.pop();
+            }
+            self.indexed -= 1;
+            self.indexed_end = block_start + start as u64;
+            bytes.drain(..start);
+            bytes.extend_from_slice(text.as_bytes());
+            self.owned
+                .push(String::from_utf8_lossy(&bytes).into_owned());
+        } else if let Some(last) = self.owned.last_mut() {
+            last.push_str(text);
+        }
+    }
+
+    /// The text of indexed `lines`, read from the checkpoint before the first.
+    /// Lines the file no longer has come out empty.
+    fn read_lines(&self, lines: Range<usize>) -> Vec<String> {
+        let first = self.skipped + lines.start;
+        let first_block = first / CHECKPOINT_LINES;
+        let last_block = (self.skipped + lines.end - 1) / CHECKPOINT_LINES;
+        let bytes = self.read(self.checkpoints[first_block]..self.block_range(last_block).end);
+        let mut text: Vec<_> = split_lines(&bytes)
+            .skip(first % CHECKPOINT_LINES)
+            .take(lines.len())
+            .map(line_text)
+            .collect();
+        text.resize(lines.len(), String::new());
+        text
+    }
+
+    /// Where the lines from checkpoint `block` up to the next one are in the file.
+    fn block_range(&self, block: usize) -> Range<u64> {
+        let end = self
+            .checkpoints
+            .get(block + 1)
+            .copied()
+            .unwrap_or(self.indexed_end);
+        self.checkpoints[block]..end
+    }
+
+    /// The bytes of the file in `range`, or as many of them as it still has.
+    fn read(&self, range: Range<u64>) -> Vec<u8> {
+        let file = self.file.as_ref().expect("indexed lines have a file");
+        let mut bytes = vec![0; (range.end - range.start) as usize];
+        let mut read = 0;
+        while read < bytes.len() {
+            match read_at(file, &mut bytes[read..], range.start + read as u64) {
+                Ok(0) => break,
+                Ok(count) => read += count,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                // What can't be read shows as missing, like a truncated file.
+                Err(_) => break,
+            }
+        }
+        bytes.truncate(read);
+        bytes
+    }
+}
+
+/// Splits `bytes` into lines, each with its line terminator.
+fn split_lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    bytes.split_inclusive(|&byte| byte == b'\n')
+}
+
+/// The text of a line read from a file, without its line terminator.
+fn line_text(mut bytes: &[u8]) -> String {
+    if let Some(line) = bytes.strip_suffix(b"\n") {
+        bytes = line.strip_suffix(b"\r").unwrap_or(line);
+    }
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempLog;
+
+    #[test]
+    fn indexed_then_owned_lines() {
+        let log = TempLog::new("store.log", b"one\r\ntwo \xff\nthree");
+        let mut store = LineStore::indexed(log.open());
+        store.push_indexed(&[0], 2, 11);
+        store.push_indexed(&[], 1, 16);
+        assert_eq!(
+            store.iter().collect::<Vec<_>>(),
+            ["one", "two \u{fffd}", "three"]
+        );
+
+        store.continue_last(" and a half");
+        store.extend(vec!["four".to_owned()]);
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.get(2), "three and a half");
+        assert_eq!(store.iter_from(3).collect::<Vec<_>>(), ["four"]);
+        let slice = store.slice(1..4).unwrap();
+        assert_eq!(
+            slice.iter().collect::<Vec<_>>(),
+            ["two \u{fffd}", "three and a half", "four"]
+        );
+
+        // Truncating the file underneath the store loses text, not the process.
+        log.write("on");
+        assert_eq!(store.get(0), "on");
+        assert_eq!(store.get(1), "");
+        assert_eq!(
+            store.iter().collect::<Vec<_>>(),
+            ["on", "", "three and a half", "four"]
+        );
+    }
+
+    #[test]
+    fn lines_between_checkpoints() {
+        let text: Vec<_> = (0..200).map(|index| format!("line {index}")).collect();
+        let log = TempLog::new("checkpoints.log", text.join("\n"));
+        let mut store = LineStore::indexed(log.open());
+        let checkpoints: Vec<_> = (0..200)
+            .step_by(CHECKPOINT_LINES)
+            .map(|index| text[..index].iter().map(|line| line.len() as u64 + 1).sum())
+            .collect();
+        let end = log.open().metadata().unwrap().len();
+        store.push_indexed(&checkpoints, 200, end);
+        assert_eq!(store.get(0), "line 0");
+        assert_eq!(store.get(63), "line 63");
+        assert_eq!(store.get(64), "line 64");
+        assert_eq!(store.get(199), "line 199");
+        assert_eq!(store.iter_from(60).collect::<Vec<_>>(), text[60..]);
+
+        let slice = store.slice(70..140).unwrap();
+        assert_eq!(slice.len(), 70);
+        assert_eq!(slice.get(0), "line 70");
+        assert_eq!(slice.iter().collect::<Vec<_>>(), text[70..140]);
+
+        // The last line, first after its checkpoint, goes on in memory.
+        let mut store = LineStore::indexed(log.open());
+        let end = checkpoints[2] + "line 128".len() as u64;
+        store.push_indexed(&checkpoints[..3], 129, end);
+        store.continue_last("0");
+        assert_eq!(store.len(), 129);
+        assert_eq!(store.get(127), "line 127");
+        assert_eq!(store.get(128), "line 1280");
+    }
+}
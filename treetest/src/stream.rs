@@ -0,0 +1,80 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Reads a log on a background thread, for input like stdin that arrives
+/// over time and can't be polled like a file, and for compressed files,
+/// which can't be indexed.
+#[derive(Debug)]
+pub struct Stream {
+    receiver: Receiver<std::io::Result<(String, bool)>>,
+    pub closed: bool, // the input ended or failed; nothing more will arrive
+    pub error: Option<std::io::Error>, // why the input ended early
+    pub invalid: usize, // lines that weren't valid UTF-8 and were decoded lossily
+}
+
+impl Stream {
+    /// Starts reading `path` (`-` for stdin) line by line.
+    pub fn spawn(path: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_owned();
+        thread::spawn(move || {
+            let reader = match logfile::open(&path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    let _ = sender.send(Err(err));
+                    return;
+                }
+            };
+            for line in logfile::lines(reader) {
+                let failed = line.is_err();
+                // Also stop once the viewer has gone away.
+                if sender.send(line).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        Self {
+            receiver,
+            closed: false,
+            error: None,
+            invalid: 0,
+        }
+    }
+
+    /// Blocks until the input ends, and takes the rest of the lines.
+    pub fn wait(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for line in self.receiver.iter() {
+            match line {
+                Ok((line, valid)) => {
+                    self.invalid += usize::from(!valid);
+                    lines.push(line);
+                }
+                Err(err) => self.error = Some(err),
+            }
+        }
+        self.closed = true;
+        lines
+    }
+
+    /// Takes the lines that arrived since the last call. Once the input has
+    /// ended, `closed` is set, along with `error` if reading it failed.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Ok((line, valid))) => {
+                    self.invalid += usize::from(!valid);
+                    lines.push(line);
+                }
+                Ok(Err(err)) => self.error = Some(err),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        lines
+    }
+}
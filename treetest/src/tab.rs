@@ -0,0 +1,1336 @@
+use crossterm::event::KeyCode;
+use logfile::LoadError;
+use ratatui::prelude::{Color, Line, Modifier, Span, Style};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tui_tree_widget::{TreeItem, TreeState};
+
+use crate::bookmarks::BOOKMARKS;
+use crate::groups::GROUPS;
+use crate::index::{Batch, Indexer};
+use crate::stats::{count_lines, roll_up};
+use crate::store::LineStore;
+use crate::stream::Stream;
+use crate::{
+    diff, fuzzy_match, AppError, Bookmarks, Change, Comparison, DiffRow, Edit, ErrorLines, Group,
+    Groups, Outline, Rules, Search, Section, Session, Stat, Stats, Tail,
+};
+
+/// Lines searched at a time, between events, so searching a big log
+/// doesn't hold up typing.
+const SEARCH_CHUNK: usize = 20_000;
+
+/// One open log file, with its own tree state, scroll position and search.
+#[must_use]
+pub struct Tab {
+    pub filename: String,        // name of the log file to view
+    pub state: TreeState<usize>, // identifiers are indices into `outline.sections`
+    pub outline: Outline,
+    pub(crate) items: Vec<TreeItem<'static, usize>>,
+    pub(crate) lines: LineStore,
+    pub(crate) log_offset: usize, // index of the first line shown in the log pane
+    pub(crate) log_height: usize, // log lines that fit in the pane at the last draw
+    pub(crate) cursor: usize,     // the line bookmarks act on: the last one jumped to or clicked
+    pub search: Option<Search>,
+    search_origin: Option<usize>, // line to jump to the first match after, once it's found
+    pub error_lines: ErrorLines,  // lines matching the error pattern
+    pub bookmarks: Bookmarks,     // saved next to the log file
+    pub groups: Option<Groups>,   // lines by correlation key, with a group pattern
+    group_offset: usize,          // index into the selected group's lines of the first shown
+    compare_mark: Option<usize>,  // section marked to be compared with another
+    pub comparison: Option<Comparison>, // shown in the log pane instead of the log
+    session: Option<Session>,     // to be restored once the log has been read
+    pub(crate) filter: Option<String>, // fuzzy filter applied to the tree
+    pub stat: Stat,               // statistic shown in the tree labels
+    stats: Vec<Stats>,            // per section, computed while `stat` is shown
+    own_stats: Vec<Stats>,        // per section without subsections, for `stats_lines` lines
+    stats_lines: usize,           // lines counted into `own_stats` so far
+    filter_saved: Option<(HashSet<Vec<usize>>, Vec<usize>)>, // opened set and selection before filtering
+    pub(crate) follow: Option<Tail>,
+    file_end: u64, // bytes of the file read into `lines`, where following picks up
+    pub(crate) stream: Option<Stream>, // stdin or a compressed file, while it is still being read
+    pub(crate) index: Option<Indexer>, // a plain file, while it is still being indexed
+    follow_bottom: bool, // keep the last line in view while following
+    pub warning: Option<String>, // problem found while loading, for the status line
+}
+
+impl Tab {
+    /// Opens the log file `filename`. Plain files are indexed in the
+    /// background and their lines read as they're shown, and stdin (`-`) and
+    /// compressed files are read in the background, so all start out empty
+    /// and their lines show up as they are found. With a `group` pattern,
+    /// lines are also grouped by its `key` capture.
+    pub fn new(
+        filename: &str,
+        rules: &Rules,
+        error: &Regex,
+        group: Option<&Regex>,
+    ) -> Result<Self, AppError> {
+        let file = match filename {
+            logfile::STDIN => None,
+            _ => index_file(filename)?,
+        };
+        let (file, index, stream, file_end) = match file {
+            Some((file, indexed, len)) => {
+                let index =
+                    Indexer::spawn(indexed, len, rules.clone(), error.clone(), group.cloned());
+                (Some(file), Some(index), None, len)
+            }
+            None => (None, None, Some(Stream::spawn(filename)), 0),
+        };
+        let (bookmarks, notes_warning) = match Bookmarks::sidecar(filename) {
+            Some(path) => match Bookmarks::load(&path) {
+                Ok(bookmarks) => (bookmarks, None),
+                Err(err) => (
+                    Bookmarks::default(),
+                    Some(format!("Can't read {}: {err}", path.display())),
+                ),
+            },
+            None => (Bookmarks::default(), None),
+        };
+
+        let mut tab = Self {
+            filename: filename.to_owned(),
+            state: TreeState::default(),
+            items: Vec::new(),
+            outline: Outline::new(rules.clone()),
+            lines: match file {
+                Some(file) => LineStore::indexed(file),
+                None => LineStore::default(),
+            },
+            log_offset: 0,
+            log_height: 0,
+            cursor: 0,
+            search: None,
+            search_origin: None,
+            error_lines: ErrorLines::new(error.clone(), Vec::<String>::new()),
+            bookmarks,
+            groups: group.map(|group| Groups::new(group.clone())),
+            group_offset: 0,
+            compare_mark: None,
+            comparison: None,
+            session: None,
+            filter: None,
+            stat: Stat::None,
+            stats: Vec::new(),
+            own_stats: Vec::new(),
+            stats_lines: 0,
+            filter_saved: None,
+            follow: None,
+            file_end,
+            stream,
+            index,
+            follow_bottom: false,
+            warning: notes_warning,
+        };
+        tab.rebuild_tree();
+        // Select the first item (if any)
+        if let Some(&first) = tab.outline.roots().first() {
+            tab.state.select(vec![first]);
+        }
+        Ok(tab)
+    }
+
+    /// The file name, or "stdin".
+    pub fn name(&self) -> &str {
+        if self.filename == logfile::STDIN {
+            "stdin"
+        } else {
+            &self.filename
+        }
+    }
+
+    /// The section of the currently selected tree node (if any).
+    pub fn selected_section(&self) -> Option<&Section> {
+        self.outline.sections.get(self.selected_section_id()?)
+    }
+
+    pub(crate) fn selected_section_id(&self) -> Option<usize> {
+        match self.state.selected() {
+            [BOOKMARKS | GROUPS, ..] => None,
+            selected => selected.last().copied(),
+        }
+    }
+
+    /// The selected group, whose lines the log pane shows instead of the log.
+    pub fn selected_group(&self) -> Option<&Group> {
+        match self.state.selected() {
+            &[GROUPS, index] => self.groups.as_ref()?.groups.get(index),
+            _ => None,
+        }
+    }
+
+    /// The lines shown in the log pane: a page of the log, or with a group
+    /// selected, of the group's lines.
+    pub(crate) fn shown_lines(&self) -> Vec<usize> {
+        match self.selected_group() {
+            Some(group) => group
+                .lines
+                .iter()
+                .skip(self.group_offset)
+                .take(self.log_height)
+                .copied()
+                .collect(),
+            None => (self.log_offset..(self.log_offset + self.log_height).min(self.lines.len()))
+                .collect(),
+        }
+    }
+
+    /// The index in `bookmarks.marks` of the selected tree node, if it's a bookmark.
+    pub fn selected_bookmark(&self) -> Option<usize> {
+        match self.state.selected() {
+            &[BOOKMARKS, index] => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The lines of `section`, subsections included.
+    pub fn section_lines(&self, section: usize) -> impl Iterator<Item = Cow<'_, str>> {
+        let section = &self.outline.sections[section];
+        let count = section
+            .end
+            .min(self.lines.len())
+            .saturating_sub(section.start);
+        self.lines.iter_from(section.start).take(count)
+    }
+
+    /// The lines of `section` in a store of their own, for reading on another thread.
+    pub fn section_store(&self, section: usize) -> std::io::Result<LineStore> {
+        let section = &self.outline.sections[section];
+        self.lines
+            .slice(section.start..section.end.min(self.lines.len()))
+    }
+
+    /// Marks the selected section, or compares the marked section with the
+    /// selected one, closing any open comparison. Pressed on the marked
+    /// section again, it drops the mark. Returns a message for the status line.
+    pub(crate) fn compare(&mut self) -> String {
+        let Some(id) = self.selected_section_id() else {
+            return "Select a section to compare".to_owned();
+        };
+        self.comparison = None;
+        let title = &self.outline.sections[id].title;
+        let mark = match self.compare_mark.take() {
+            None => {
+                self.compare_mark = Some(id);
+                return format!("Marked {title}; select another section and press = to compare");
+            }
+            Some(mark) if mark == id => return format!("Unmarked {title}"),
+            Some(mark) => mark,
+        };
+        let left: Vec<_> = self.section_lines(mark).collect();
+        let right: Vec<_> = self.section_lines(id).collect();
+        let (left_start, right_start) = (
+            self.outline.sections[mark].start,
+            self.outline.sections[id].start,
+        );
+        let rows: Vec<_> = diff(&left, &right)
+            .into_iter()
+            .map(|row| DiffRow {
+                left: row.left.map(|line| left_start + line),
+                right: row.right.map(|line| right_start + line),
+                ..row
+            })
+            .collect();
+        let count = |edit| rows.iter().filter(|row| row.edit == edit).count();
+        let message = format!(
+            "{} changed, {} removed, {} added line(s)",
+            count(Edit::Changed),
+            count(Edit::Removed),
+            count(Edit::Added)
+        );
+        self.comparison = Some(Comparison {
+            left: mark,
+            right: id,
+            rows,
+            offset: 0,
+        });
+        message
+    }
+
+    /// Writes the selected section to the file at `path`, returning the
+    /// number of lines written.
+    pub(crate) fn export_selected(&self, path: &str) -> std::io::Result<usize> {
+        let Some(id) = self.selected_section_id() else {
+            return Ok(0);
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        let count = write_lines(&mut out, self.section_lines(id))?;
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Scrolls the log pane so the selected section's header is the top line,
+    /// or brings the selected bookmark into view.
+    pub fn scroll_to_selected(&mut self) {
+        if self.selected_group().is_some() {
+            self.group_offset = 0;
+        } else if let Some(index) = self.selected_bookmark() {
+            let line = self.bookmarks.marks[index].line;
+            self.scroll_into_view(line);
+        } else if let Some(start) = self.selected_section().map(|section| section.start) {
+            self.log_offset = start;
+            self.cursor = start;
+            self.follow_bottom = false;
+        }
+    }
+
+    /// The line bookmarks act on: the cursor, kept within the log pane.
+    pub fn current_line(&self) -> usize {
+        if self.selected_group().is_some() {
+            let shown = self.shown_lines();
+            return match shown.first() {
+                Some(&first) if !shown.contains(&self.cursor) => first,
+                _ => self.cursor,
+            };
+        }
+        let last = (self.log_offset + self.log_height)
+            .min(self.lines.len())
+            .saturating_sub(1);
+        self.cursor
+            .clamp(self.log_offset, last.max(self.log_offset))
+    }
+
+    /// Bookmarks the current line, or removes its bookmark. Returns a message
+    /// for the status line.
+    pub(crate) fn toggle_bookmark(&mut self) -> String {
+        let line = self.current_line();
+        if line >= self.lines.len() {
+            return "No line to bookmark".to_owned();
+        }
+        let marked = self.bookmarks.toggle(line, &self.lines.get(line));
+        self.bookmarks_changed();
+        self.save_bookmarks().unwrap_or(if marked {
+            format!("Bookmarked line {}", line + 1)
+        } else {
+            format!("Removed the bookmark on line {}", line + 1)
+        })
+    }
+
+    /// The note on the current line's bookmark.
+    pub(crate) fn current_note(&self) -> Option<&str> {
+        let index = self.bookmarks.find(self.current_line())?;
+        Some(&self.bookmarks.marks[index].note)
+    }
+
+    /// Sets the note on the current line, bookmarking it if needed. Returns
+    /// an error message if the bookmarks can't be saved.
+    pub(crate) fn set_note(&mut self, note: &str) -> Option<String> {
+        let line = self.current_line();
+        if line >= self.lines.len() {
+            return None;
+        }
+        self.bookmarks.set_note(line, &self.lines.get(line), note);
+        self.bookmarks_changed();
+        self.save_bookmarks()
+    }
+
+    fn bookmarks_changed(&mut self) {
+        self.rebuild_tree();
+        // The bookmark that was selected may be gone.
+        if self.selected_bookmark().is_some() && !contains_path(&self.items, self.state.selected())
+        {
+            self.state.select(Vec::new());
+            self.reveal_line(self.current_line());
+        }
+    }
+
+    fn save_bookmarks(&self) -> Option<String> {
+        let path = Bookmarks::sidecar(&self.filename)?;
+        let err = self.bookmarks.save(&path).err()?;
+        Some(format!("Can't save {}: {err}", path.display()))
+    }
+
+    /// Jumps to the next bookmarked line.
+    pub fn next_bookmark(&mut self) -> bool {
+        match self.bookmarks.next_after(self.current_line()) {
+            Some(line) => self.show_line(line),
+            None => false,
+        }
+    }
+
+    pub fn previous_bookmark(&mut self) -> bool {
+        match self.bookmarks.previous_before(self.current_line()) {
+            Some(line) => self.show_line(line),
+            None => false,
+        }
+    }
+
+    /// The file and line (counted from 1) to open in an editor, or why the
+    /// log can't be edited.
+    pub fn edit_target(&self) -> Result<(&str, usize), String> {
+        if self.filename == logfile::STDIN {
+            return Err("stdin can't be edited".to_owned());
+        }
+        if logfile::compression(&self.filename)
+            .is_ok_and(|compression| compression != logfile::Compression::None)
+        {
+            return Err(format!("Can't edit {}: it's compressed", self.filename));
+        }
+        Ok((&self.filename, self.current_line() + 1))
+    }
+
+    /// Reads the file again, e.g. after it was edited, keeping the opened
+    /// nodes, the selection and the scroll position as far as the new
+    /// sections allow. Sections are matched by their titles.
+    pub fn reload(&mut self) -> Result<(), AppError> {
+        let group = self.groups.as_ref().map(|groups| &groups.regex);
+        let mut tab = Tab::new(
+            &self.filename,
+            self.outline.rules(),
+            &self.error_lines.regex,
+            group,
+        )?;
+        tab.finish_loading()
+            .map_err(|source| LoadError::read(&self.filename, source))?;
+        tab.stat = self.stat;
+        if let Some(search) = &self.search {
+            tab.set_search(Some(Search::new(&search.query)));
+        }
+        tab.rebuild_tree();
+
+        for path in self.state.opened() {
+            if let Some(path) = translate_path(path, &self.outline, &tab.outline) {
+                tab.state.open(path);
+            }
+        }
+        // The view moves along with the selected section if lines were
+        // added or removed above it.
+        let mut shift = 0;
+        let selected = self.state.selected();
+        let kept = (1..=selected.len()).rev().find_map(|len| {
+            let path = translate_path(&selected[..len], &self.outline, &tab.outline)?;
+            contains_path(&tab.items, &path).then_some((len, path))
+        });
+        if let Some((len, path)) = kept {
+            if !matches!(path[0], BOOKMARKS | GROUPS) {
+                let old = self.outline.sections[selected[len - 1]].start;
+                let new = tab.outline.sections[path[len - 1]].start;
+                shift = new as isize - old as isize;
+            }
+            tab.state.select(path);
+        }
+        tab.log_height = self.log_height;
+        tab.log_offset = self
+            .log_offset
+            .saturating_add_signed(shift)
+            .min(tab.last_page());
+        tab.cursor = self.cursor.saturating_add_signed(shift);
+        if self.follow.is_some() {
+            tab.warning = tab.toggle_follow().or(tab.warning);
+        }
+        *self = tab;
+        Ok(())
+    }
+
+    /// Where the log was left, to restore when it's opened again. The
+    /// Bookmarks and Groups nodes aren't included. Stdin has no session.
+    pub fn session(&self) -> Option<Session> {
+        if self.filename == logfile::STDIN {
+            return None;
+        }
+        // Quitting before the log was read keeps the session that was loaded.
+        if let Some(session) = &self.session {
+            return Some(session.clone());
+        }
+        let mut session = Session::for_file(&self.filename).ok()?;
+        // Bookmark and group nodes have no titles, and aren't kept.
+        session.opened = self
+            .state
+            .opened()
+            .iter()
+            .filter_map(|path| path_titles(path, &self.outline))
+            .collect();
+        session.opened.sort();
+        session.selected = path_titles(self.state.selected(), &self.outline).unwrap_or_default();
+        session.log_offset = self.log_offset;
+        session.search = self.search.as_ref().map(|search| search.query.clone());
+        Some(session)
+    }
+
+    /// Opens the tree nodes, selection, scroll position and search of
+    /// `session`, or does so once the log has been read if it's still
+    /// being read. Nodes that are gone are skipped.
+    pub fn restore(&mut self, session: Session) {
+        if self.index.is_some() || self.stream.is_some() {
+            self.session = Some(session);
+            return;
+        }
+        if let Some(query) = &session.search {
+            self.set_search(Some(Search::new(query)));
+        }
+        for titles in session.opened {
+            if let Some(path) = titles_path(&titles, &self.outline) {
+                self.state.open(path);
+            }
+        }
+        if let Some(path) = titles_path(&session.selected, &self.outline) {
+            self.state.select(path);
+        }
+        self.log_offset = session.log_offset.min(self.last_page());
+        self.cursor = self.log_offset;
+    }
+
+    /// Starts or stops following the file. Returns an error message if it
+    /// can't be followed.
+    pub fn toggle_follow(&mut self) -> Option<String> {
+        if let Some(tail) = self.follow.take() {
+            self.file_end = tail.position();
+            return None;
+        }
+        if self.filename == logfile::STDIN {
+            return Some("stdin is always read as it arrives".to_owned());
+        }
+        // New data is read raw, which only makes sense for plain text.
+        if logfile::compression(&self.filename)
+            .is_ok_and(|compression| compression != logfile::Compression::None)
+        {
+            return Some(format!("Can't follow {}: it's compressed", self.filename));
+        }
+        match Tail::new(&self.filename, self.file_end) {
+            Ok(tail) => {
+                self.follow = Some(tail);
+                self.follow_bottom = true;
+                None
+            }
+            Err(err) => Some(format!("Can't follow {}: {err}", self.filename)),
+        }
+    }
+
+    /// Picks up whatever was written to the followed file since the last poll.
+    /// Noteworthy events are reported through `message`.
+    pub fn poll_follow(&mut self, message: &mut Option<String>) -> bool {
+        // Following only starts after the lines already in the file are indexed.
+        if let Some(index) = &mut self.index {
+            let batches = index.poll();
+            let (done, invalid, error) = (index.done, index.invalid, index.error.take());
+            let update = !batches.is_empty() || done;
+            self.add_batches(batches);
+            if done {
+                // Less than the whole file if reading it failed part way.
+                self.file_end = self.index.take().map_or(0, |index| index.indexed);
+                self.indexed(invalid, error, message);
+            }
+            return update;
+        }
+        if let Some(stream) = &mut self.stream {
+            let lines = stream.poll();
+            let update = !lines.is_empty();
+            if update {
+                self.append_lines(lines);
+            }
+            if let Some(stream) = self.stream.take_if(|stream| stream.closed) {
+                self.indexed(stream.invalid, stream.error, message);
+                return true;
+            }
+            return update;
+        }
+        let Some(tail) = &mut self.follow else {
+            return false;
+        };
+        match tail.poll() {
+            Ok(None) => false,
+            Ok(Some(Change::Appended {
+                continuation,
+                lines,
+            })) => {
+                if let Some(continuation) = continuation {
+                    // Only happens if the line was counted before following started.
+                    if self.stats_lines == self.lines.len() {
+                        self.reset_stats();
+                    }
+                    self.lines.continue_last(&continuation);
+                }
+                self.append_lines(lines);
+                true
+            }
+            Ok(Some(Change::Replaced(lines))) => {
+                self.replace_lines(lines);
+                *message = Some(format!("{} was truncated or replaced", self.filename));
+                true
+            }
+            Err(err) => {
+                *message = Some(format!("Stopped following {}: {err}", self.filename));
+                self.file_end = tail.position();
+                self.follow = None;
+                true
+            }
+        }
+    }
+
+    /// Blocks until the whole log has been read, for use without the UI.
+    pub fn finish_loading(&mut self) -> std::io::Result<()> {
+        if let Some(mut index) = self.index.take() {
+            let batches = index.wait();
+            self.add_batches(batches);
+            self.file_end = index.indexed;
+            let mut message = None;
+            self.indexed(index.invalid, index.error.take(), &mut message);
+            self.warning = message.or(self.warning.take());
+        }
+        if let Some(mut stream) = self.stream.take() {
+            let lines = stream.wait();
+            self.append_lines(lines);
+            let mut message = None;
+            self.indexed(stream.invalid, None, &mut message);
+            self.warning = message.or(self.warning.take());
+            if let Some(err) = stream.error {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the lines of an indexed file as the indexer finds them.
+    fn add_batches(&mut self, batches: Vec<Batch>) {
+        let first = self.lines.len();
+        let sections = self.outline.sections.len();
+        for batch in batches {
+            let mut next = batch.first;
+            for (line, depth, title) in &batch.headers {
+                self.outline.push_plain(line - next);
+                self.outline.push_header(Some((*depth, title)));
+                next = line + 1;
+            }
+            self.outline.push_plain(batch.first + batch.lines - next);
+            self.error_lines.lines.extend(batch.errors);
+            if let Some(groups) = &mut self.groups {
+                for (line, key) in &batch.keys {
+                    groups.push(*line, key);
+                }
+            }
+            self.lines
+                .push_indexed(&batch.checkpoints, batch.lines, batch.end);
+        }
+        self.lines_added(first, sections);
+    }
+
+    /// Finishes up once the whole log has been read, reporting lines that
+    /// weren't valid UTF-8 and why reading stopped early, if it did.
+    fn indexed(
+        &mut self,
+        invalid: usize,
+        error: Option<std::io::Error>,
+        message: &mut Option<String>,
+    ) {
+        self.bookmarks.relocate(&self.lines);
+        if self.stat != Stat::None || !self.bookmarks.is_empty() {
+            self.rebuild_tree();
+        }
+        if let Some(session) = self.session.take() {
+            self.restore(session);
+        }
+        if invalid > 0 {
+            *message = Some(format!(
+                "{}: {invalid} invalid UTF-8 line(s) shown with replacement characters",
+                self.name()
+            ));
+        }
+        if let Some(err) = error {
+            *message = Some(format!(
+                "Stopped reading {} after {} lines: {err}",
+                self.name(),
+                self.lines.len()
+            ));
+        }
+    }
+
+    fn append_lines(&mut self, lines: Vec<String>) {
+        let first = self.lines.len();
+        let sections = self.outline.sections.len();
+        for line in &lines {
+            self.outline.push(line);
+        }
+        self.error_lines.extend(&lines, first);
+        if let Some(groups) = &mut self.groups {
+            groups.extend(&lines, first);
+        }
+        self.lines.extend(lines);
+        self.lines_added(first, sections);
+    }
+
+    /// Updates the search and tree for the lines from `first` on, which were
+    /// just added along with the sections from `sections` on.
+    fn lines_added(&mut self, first: usize, sections: usize) {
+        let mut matched = false;
+        // A search that hasn't caught up yet gets to the new lines by itself.
+        if let Some(search) = self
+            .search
+            .as_mut()
+            .filter(|search| search.searched == first)
+        {
+            let before = search.matches.len();
+            search.extend(self.lines.iter_from(first));
+            matched = search.matches.len() > before;
+        }
+        let stats = self.stat != Stat::None && self.index.is_none();
+        // Group line counts change with nearly every line.
+        let grouped = self.groups.is_some();
+        if matched || self.outline.sections.len() > sections || stats || grouped {
+            self.rebuild_tree();
+        }
+        // A log that started without sections gets a selection once one arrives.
+        if self.state.selected().is_empty() {
+            if let Some(&first) = self.outline.roots().first() {
+                self.state.select(vec![first]);
+            }
+        }
+    }
+
+    fn replace_lines(&mut self, lines: Vec<String>) {
+        self.outline = Outline::parse(&lines, self.outline.rules().clone());
+        if let Some(search) = &mut self.search {
+            *search = Search::new(&search.query);
+            search.extend(&lines);
+        }
+        self.error_lines = ErrorLines::new(self.error_lines.regex.clone(), &lines);
+        if let Some(groups) = &mut self.groups {
+            groups.clear();
+            groups.extend(&lines, 0);
+        }
+        self.lines = LineStore::new(lines);
+        self.reset_stats();
+        self.bookmarks.relocate(&self.lines);
+        self.compare_mark = None;
+        self.comparison = None;
+        self.log_offset = self.log_offset.min(self.last_page());
+        self.state.close_all();
+        self.state.select(Vec::new());
+        if let Some(&first) = self.outline.roots().first() {
+            self.state.select(vec![first]);
+        }
+        self.rebuild_tree();
+    }
+
+    /// Keeps the last line in view if the user hasn't scrolled away from it.
+    pub(crate) fn stick_to_bottom(&mut self) {
+        let following = self.follow.is_some() || self.stream.is_some();
+        if following && self.follow_bottom && self.selected_group().is_none() {
+            self.set_log_offset(self.last_page());
+        }
+    }
+
+    /// Selects the innermost section containing `line`, opening its ancestors,
+    /// so the tree follows the log pane.
+    pub(crate) fn reveal_line(&mut self, line: usize) -> bool {
+        let Some(section) = self.outline.section_at(line) else {
+            return false;
+        };
+        let path = self.outline.path(section);
+        for depth in 1..path.len() {
+            self.state.open(path[..depth].to_vec());
+        }
+        self.state.select(path)
+    }
+
+    pub(crate) fn tree_key(&mut self, code: KeyCode) -> bool {
+        let selected = self.state.selected().to_vec();
+        let update = match code {
+            KeyCode::Char('\n' | ' ') => self.state.toggle_selected(),
+            KeyCode::Left => {
+                // Always want there to be a selection, so don't do anything
+                // if a first-level item is selected and it's not opened.
+                if self.state.selected().len() == 1
+                    && !self.state.opened().contains(self.state.selected())
+                {
+                    false
+                } else {
+                    self.state.key_left()
+                }
+            }
+
+            KeyCode::Right => self.state.key_right(),
+            KeyCode::Down => self.state.key_down(),
+            KeyCode::Up => self.state.key_up(),
+            KeyCode::Esc => self.state.select_first(),
+            KeyCode::Home => self.state.select_first(),
+            KeyCode::End => self.state.select_last(),
+            KeyCode::PageDown => self.state.scroll_down(3),
+            KeyCode::PageUp => self.state.scroll_up(3),
+            KeyCode::Char('+') => {
+                open_all(&mut self.state, &self.items, &mut Vec::new());
+                true
+            }
+            KeyCode::Char('-') => self.open_to_depth(1),
+            KeyCode::Char(digit @ '1'..='9') => self.open_to_depth(digit as usize - '0' as usize),
+            KeyCode::Char('c') => self.collapse_siblings(),
+            _ => false,
+        };
+        if self.state.selected() != selected {
+            self.scroll_to_selected();
+        }
+        update
+    }
+
+    pub(crate) fn log_key(&mut self, code: KeyCode) -> bool {
+        let page = self.log_height.saturating_sub(1).max(1);
+        match code {
+            KeyCode::Down => self.scroll_log_down(1),
+            KeyCode::Up => self.scroll_log_up(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_log_down(page),
+            KeyCode::PageUp => self.scroll_log_up(page),
+            KeyCode::Home => self.scroll_log_up(usize::MAX),
+            KeyCode::End => self.scroll_log_down(usize::MAX),
+            KeyCode::Esc => self.comparison.take().is_some(),
+            _ => false,
+        }
+    }
+
+    /// Replaces the search, jumping to the first match at or after `origin`
+    /// once it's found.
+    pub(crate) fn search_for(&mut self, query: &str, origin: usize) {
+        self.search = Some(Search::new(query));
+        self.search_origin = Some(origin);
+        self.search_more();
+        self.rebuild_tree();
+    }
+
+    /// Replaces the search, and searches the first lines for it.
+    pub(crate) fn set_search(&mut self, search: Option<Search>) {
+        self.search = search;
+        self.search_origin = None;
+        self.search_more();
+        self.rebuild_tree();
+    }
+
+    /// Searches the next `SEARCH_CHUNK` lines, if the search isn't done yet.
+    pub fn search_more(&mut self) -> bool {
+        let len = self.lines.len();
+        let Some(search) = self.search.as_mut().filter(|search| !search.is_done(len)) else {
+            return false;
+        };
+        let before = search.matches.len();
+        search.extend(self.lines.iter_from(search.searched).take(SEARCH_CHUNK));
+        let found = search.matches.len() > before;
+        // Seeking wraps around, so it waits for a match after the origin or the end.
+        let passed = |origin| search.matches.last().is_some_and(|m| m.line >= origin);
+        if let Some(origin) = self
+            .search_origin
+            .filter(|&origin| passed(origin) || search.is_done(len))
+        {
+            self.search_origin = None;
+            search.seek(origin);
+            self.show_match();
+        }
+        if found {
+            self.rebuild_tree();
+        }
+        true
+    }
+
+    /// Shows the next statistic in the tree labels.
+    pub fn cycle_stat(&mut self) -> bool {
+        self.stat = self.stat.next();
+        self.rebuild_tree();
+        true
+    }
+
+    pub(crate) fn rebuild_tree(&mut self) {
+        // Stats cover the whole log, so they wait until all of it is indexed.
+        self.stats = if self.stat == Stat::None || self.index.is_some() {
+            Vec::new()
+        } else {
+            self.count_stats();
+            roll_up(&self.outline, &self.own_stats)
+        };
+        self.items = tree_items(
+            &self.outline,
+            self.search.as_ref(),
+            self.filter.as_deref(),
+            &self.stats,
+            self.stat,
+            self.outline.roots(),
+        );
+        let filter = self.filter.as_deref();
+        if let Some(item) = self
+            .groups
+            .as_ref()
+            .and_then(|groups| groups_item(groups, filter))
+        {
+            self.items.push(item);
+        }
+        if let Some(item) = bookmarks_item(&self.bookmarks, &self.lines, self.filter.as_deref()) {
+            self.items.push(item);
+        }
+    }
+
+    /// Counts the lines added since the stats were last brought up to date.
+    fn count_stats(&mut self) {
+        let sections = self.outline.sections.len();
+        self.own_stats.resize(sections, Stats::default());
+        // A followed file's last line isn't counted while more of it may come.
+        let open = self.follow.as_ref().is_some_and(Tail::last_line_open);
+        let end = self.lines.len() - usize::from(open && !self.lines.is_empty());
+        count_lines(
+            &mut self.own_stats,
+            &self.outline,
+            self.lines
+                .iter_from(self.stats_lines)
+                .take(end.saturating_sub(self.stats_lines)),
+            self.stats_lines,
+            &self.error_lines.regex,
+        );
+        self.stats_lines = self.stats_lines.max(end);
+    }
+
+    /// Makes the stats be counted again from the start, after lines changed.
+    fn reset_stats(&mut self) {
+        self.own_stats.clear();
+        self.stats_lines = 0;
+    }
+
+    /// Opens the tree so exactly the nodes `depth` levels down are shown.
+    fn open_to_depth(&mut self, depth: usize) -> bool {
+        self.state.close_all();
+        open_to_depth(&mut self.state, &self.items, depth, &mut Vec::new());
+        self.select_visible();
+        true
+    }
+
+    /// Closes every node that isn't on the way to the selected one or inside it.
+    fn collapse_siblings(&mut self) -> bool {
+        let selected = self.state.selected().to_vec();
+        let opened = self.state.opened().clone();
+        for path in opened {
+            if !selected.starts_with(&path) && !path.starts_with(&selected) {
+                self.state.close(&path);
+            }
+        }
+        true
+    }
+
+    /// Moves the selection up to its nearest ancestor that can be seen.
+    fn select_visible(&mut self) {
+        let selected = self.state.selected().to_vec();
+        let shown = (1..selected.len())
+            .find(|&len| !self.state.opened().contains(&selected[..len]))
+            .unwrap_or(selected.len());
+        if shown < selected.len() {
+            self.state.select(selected[..shown].to_vec());
+        }
+    }
+
+    /// Starts filtering the tree, remembering its state so it can be restored.
+    pub(crate) fn start_filter(&mut self) {
+        self.filter = Some(String::new());
+        self.filter_saved = Some((self.state.opened().clone(), self.state.selected().to_vec()));
+    }
+
+    /// Narrows the tree to `query` and keeps a visible node selected.
+    pub(crate) fn apply_filter(&mut self, query: &str) {
+        self.filter = Some(query.to_owned());
+        let selected = self.state.selected().to_vec();
+        self.rebuild_tree();
+        open_all(&mut self.state, &self.items, &mut Vec::new());
+        if !contains_path(&self.items, self.state.selected()) {
+            let first = self
+                .outline
+                .sections
+                .iter()
+                .position(|section| fuzzy_match(query, &section.title).is_some());
+            if let Some(first) = first {
+                self.state.select(self.outline.path(first));
+            }
+        }
+        if self.state.selected() != selected {
+            self.scroll_to_selected();
+        }
+    }
+
+    /// Shows the full tree again with the opened set from before filtering.
+    /// Keeps the node chosen while filtering selected, or goes back to the
+    /// previous selection.
+    pub(crate) fn end_filter(&mut self, keep_selection: bool) {
+        self.filter = None;
+        let chosen = self.state.selected().to_vec();
+        if let Some((opened, selected)) = self.filter_saved.take() {
+            self.state.close_all();
+            for path in opened {
+                self.state.open(path);
+            }
+            if keep_selection && !chosen.is_empty() {
+                for depth in 1..chosen.len() {
+                    self.state.open(chosen[..depth].to_vec());
+                }
+                self.state.select(chosen);
+            } else {
+                self.state.select(selected);
+            }
+        }
+        self.rebuild_tree();
+        self.scroll_to_selected();
+    }
+
+    pub fn next_match(&mut self) -> bool {
+        self.search.as_mut().and_then(Search::next_match).is_some() && self.show_match()
+    }
+
+    pub fn previous_match(&mut self) -> bool {
+        self.search
+            .as_mut()
+            .and_then(Search::previous_match)
+            .is_some()
+            && self.show_match()
+    }
+
+    /// Jumps to the next line matching the error pattern.
+    pub fn next_error(&mut self) -> bool {
+        match self.error_lines.next_from(self.log_offset) {
+            Some(line) => self.show_line(line),
+            None => false,
+        }
+    }
+
+    pub fn previous_error(&mut self) -> bool {
+        match self.error_lines.previous_from(self.log_offset) {
+            Some(line) => self.show_line(line),
+            None => false,
+        }
+    }
+
+    /// Scrolls the current match into view and selects its section.
+    fn show_match(&mut self) -> bool {
+        match self
+            .search
+            .as_ref()
+            .and_then(Search::current_match)
+            .map(|m| m.line)
+        {
+            Some(line) => self.show_line(line),
+            None => false,
+        }
+    }
+
+    /// Scrolls `line` into view and selects its section.
+    pub(crate) fn show_line(&mut self, line: usize) -> bool {
+        self.scroll_into_view(line);
+        self.reveal_line(line);
+        true
+    }
+
+    /// Moves the cursor to `line`, scrolling it a third of the way down the
+    /// pane if it wasn't visible.
+    fn scroll_into_view(&mut self, line: usize) {
+        if !(self.log_offset..self.log_offset + self.log_height).contains(&line) {
+            self.log_offset = line.saturating_sub(self.log_height / 3);
+            self.follow_bottom = false;
+        }
+        self.cursor = line;
+    }
+
+    /// Offset at which the last line is at the bottom of the log pane.
+    fn last_page(&self) -> usize {
+        self.lines.len().saturating_sub(self.log_height)
+    }
+
+    pub(crate) fn scroll_log_down(&mut self, lines: usize) -> bool {
+        if let Some(comparison) = &mut self.comparison {
+            let last_page = comparison.rows.len().saturating_sub(self.log_height);
+            let offset = comparison.offset.saturating_add(lines).min(last_page);
+            return std::mem::replace(&mut comparison.offset, offset) != offset;
+        }
+        if let Some(group) = self.selected_group() {
+            let last_page = group.lines.len().saturating_sub(self.log_height);
+            let offset = self.group_offset.saturating_add(lines).min(last_page);
+            return std::mem::replace(&mut self.group_offset, offset) != offset;
+        }
+        // Never scroll past the last full page, but don't jump back if a
+        // selected section already put us there.
+        let last_page = self.last_page();
+        let offset = self
+            .log_offset
+            .saturating_add(lines)
+            .min(last_page.max(self.log_offset));
+        self.set_log_offset(offset)
+    }
+
+    pub(crate) fn scroll_log_up(&mut self, lines: usize) -> bool {
+        if let Some(comparison) = &mut self.comparison {
+            let offset = comparison.offset.saturating_sub(lines);
+            return std::mem::replace(&mut comparison.offset, offset) != offset;
+        }
+        if self.selected_group().is_some() {
+            let offset = self.group_offset.saturating_sub(lines);
+            return std::mem::replace(&mut self.group_offset, offset) != offset;
+        }
+        self.set_log_offset(self.log_offset.saturating_sub(lines))
+    }
+
+    pub(crate) fn set_log_offset(&mut self, offset: usize) -> bool {
+        // Scrolling away from the bottom stops the view from following new lines.
+        self.follow_bottom = offset >= self.last_page();
+        if offset == self.log_offset {
+            return false;
+        }
+        self.log_offset = offset;
+        self.reveal_line(offset);
+        true
+    }
+}
+
+/// Opens the file at `path` for indexing if it is a regular, uncompressed
+/// file: once for reading shown lines, and once for the indexer, along with
+/// its current size.
+fn index_file(path: &str) -> Result<Option<(File, File, u64)>, AppError> {
+    let open_error = |source| LoadError::open(path, source);
+    let file = File::open(path).map_err(open_error)?;
+    let metadata = file.metadata().map_err(open_error)?;
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    if logfile::compression(path).map_err(open_error)? != logfile::Compression::None {
+        return Ok(None);
+    }
+    let indexed = File::open(path).map_err(open_error)?;
+    Ok(Some((file, indexed, metadata.len())))
+}
+
+/// Writes `lines`, each followed by a newline, and returns how many there were.
+pub(crate) fn write_lines<S: AsRef<str>>(
+    out: &mut impl Write,
+    lines: impl IntoIterator<Item = S>,
+) -> std::io::Result<usize> {
+    let mut count = 0;
+    for line in lines {
+        writeln!(out, "{}", line.as_ref())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Builds the `TreeItem`s for `sections` and, recursively, their subsections.
+/// With a search, each label gets a badge counting the matches in its section,
+/// and with `stats`, a badge showing `stat`.
+/// With a filter, only sections whose title fuzzy-matches it are kept, along
+/// with their ancestors, and the matched characters are highlighted.
+fn tree_items(
+    outline: &Outline,
+    search: Option<&Search>,
+    filter: Option<&str>,
+    stats: &[Stats],
+    stat: Stat,
+    sections: &[usize],
+) -> Vec<TreeItem<'static, usize>> {
+    sections
+        .iter()
+        .filter_map(|&id| {
+            let section = &outline.sections[id];
+            let children = tree_items(outline, search, filter, stats, stat, &section.children);
+            let matched = match filter {
+                Some(filter) => fuzzy_match(filter, &section.title),
+                None => Some(Vec::new()),
+            };
+            if matched.is_none() && children.is_empty() {
+                return None;
+            }
+
+            let mut label = highlight_chars(&section.title, &matched.unwrap_or_default());
+            let count = search.map_or(0, |search| {
+                search.in_lines(section.start..section.end).len()
+            });
+            if count > 0 {
+                label.push(Span::styled(
+                    format!(" [{count}]"),
+                    Style::new().fg(Color::Yellow),
+                ));
+            }
+            if let Some(stats) = stats.get(id) {
+                label.extend(stats.badge(stat));
+            }
+            let label = Line::from(label);
+            Some(if children.is_empty() {
+                TreeItem::new_leaf(id, label)
+            } else {
+                TreeItem::new(id, label, children).expect("section indices are unique")
+            })
+        })
+        .collect()
+}
+
+/// Builds the "Groups" node, with a node for each key counting its lines.
+/// With a filter, only matching keys are kept. There's no node without groups.
+fn groups_item(groups: &Groups, filter: Option<&str>) -> Option<TreeItem<'static, usize>> {
+    let children: Vec<_> = groups
+        .groups
+        .iter()
+        .enumerate()
+        .filter_map(|(index, group)| {
+            let matched = match filter {
+                Some(filter) => fuzzy_match(filter, &group.key)?,
+                None => Vec::new(),
+            };
+            let mut label = highlight_chars(&group.key, &matched);
+            label.push(Span::styled(
+                format!(" [{}]", group.lines.len()),
+                Style::new().fg(Color::Cyan),
+            ));
+            Some(TreeItem::new_leaf(index, Line::from(label)))
+        })
+        .collect();
+    if children.is_empty() {
+        return None;
+    }
+    let label = Line::from(vec![
+        Span::raw("Groups"),
+        Span::styled(
+            format!(" [{}]", children.len()),
+            Style::new().fg(Color::Cyan),
+        ),
+    ]);
+    Some(TreeItem::new(GROUPS, label, children).expect("group indices are unique"))
+}
+
+/// Builds the "Bookmarks" node, listing each bookmark's line number and its
+/// note, or without one, its text. With a filter, only matching bookmarks are
+/// kept. There's no node without bookmarks.
+fn bookmarks_item(
+    bookmarks: &Bookmarks,
+    lines: &LineStore,
+    filter: Option<&str>,
+) -> Option<TreeItem<'static, usize>> {
+    let children: Vec<_> = bookmarks
+        .marks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, mark)| {
+            let text = if !mark.note.is_empty() {
+                mark.note.clone()
+            } else if mark.line < lines.len() {
+                lines.get(mark.line).trim().to_owned()
+            } else {
+                String::new()
+            };
+            let label = format!("{}: {text}", mark.line + 1);
+            let matched = match filter {
+                Some(filter) => fuzzy_match(filter, &label)?,
+                None => Vec::new(),
+            };
+            Some(TreeItem::new_leaf(
+                index,
+                Line::from(highlight_chars(&label, &matched)),
+            ))
+        })
+        .collect();
+    if children.is_empty() {
+        return None;
+    }
+    let label = Line::from(vec![
+        Span::raw("Bookmarks"),
+        Span::styled(
+            format!(" [{}]", children.len()),
+            Style::new().fg(Color::Cyan),
+        ),
+    ]);
+    Some(TreeItem::new(BOOKMARKS, label, children).expect("bookmark indices are unique"))
+}
+
+/// Splits `text` into spans, emphasizing the chars at `positions`.
+fn highlight_chars(text: &str, positions: &[usize]) -> Vec<Span<'static>> {
+    if positions.is_empty() {
+        return vec![Span::raw(text.to_owned())];
+    }
+    let style = Style::new()
+        .fg(Color::LightYellow)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (index, c) in text.chars().enumerate() {
+        let matched = positions.contains(&index);
+        if matched != run_matched && !run.is_empty() {
+            let content = std::mem::take(&mut run);
+            spans.push(if run_matched {
+                Span::styled(content, style)
+            } else {
+                Span::raw(content)
+            });
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    spans.push(if run_matched {
+        Span::styled(run, style)
+    } else {
+        Span::raw(run)
+    });
+    spans
+}
+
+/// Opens every node of `items` that has children.
+fn open_all(state: &mut TreeState<usize>, items: &[TreeItem<'_, usize>], path: &mut Vec<usize>) {
+    for item in items {
+        if item.children().is_empty() {
+            continue;
+        }
+        path.push(*item.identifier());
+        state.open(path.clone());
+        open_all(state, item.children(), path);
+        path.pop();
+    }
+}
+
+/// The tree path in `to` of the section at `path` in `from`, going by the
+/// titles from the top. Paths into the bookmarks or groups stay as they are.
+fn translate_path(path: &[usize], from: &Outline, to: &Outline) -> Option<Vec<usize>> {
+    if let Some(&(BOOKMARKS | GROUPS)) = path.first() {
+        return Some(path.to_vec());
+    }
+    titles_path(&path_titles(path, from)?, to)
+}
+
+/// The titles of the sections on `path`, from the top, if it's the path of a section.
+fn path_titles(path: &[usize], outline: &Outline) -> Option<Vec<String>> {
+    if path.is_empty() {
+        return None;
+    }
+    path.iter()
+        .map(|&id| {
+            outline
+                .sections
+                .get(id)
+                .map(|section| section.title.clone())
+        })
+        .collect()
+}
+
+/// The path of the section with the titles `titles` from the top, if there is one.
+fn titles_path(titles: &[String], outline: &Outline) -> Option<Vec<usize>> {
+    outline.find_titles(titles).map(|id| outline.path(id))
+}
+
+/// Opens the nodes of `items` that are less than `depth` levels down,
+/// counting the top level as 1.
+fn open_to_depth(
+    state: &mut TreeState<usize>,
+    items: &[TreeItem<'_, usize>],
+    depth: usize,
+    path: &mut Vec<usize>,
+) {
+    if path.len() + 1 >= depth {
+        return;
+    }
+    for item in items {
+        if item.children().is_empty() {
+            continue;
+        }
+        path.push(*item.identifier());
+        state.open(path.clone());
+        open_to_depth(state, item.children(), depth, path);
+        path.pop();
+    }
+}
+
+/// Whether the identifier `path` leads to a node of `items`.
+fn contains_path(items: &[TreeItem<'_, usize>], path: &[usize]) -> bool {
+    match path.split_first() {
+        None => false,
+        Some((first, [])) => items.iter().any(|item| item.identifier() == first),
+        Some((first, rest)) => items
+            .iter()
+            .find(|item| item.identifier() == first)
+            .is_some_and(|item| contains_path(item.children(), rest)),
+    }
+}